@@ -0,0 +1,19 @@
+-- Every change to a forecast's data is kept as a new revision, so the latest
+-- revision is the forecast's current state.
+CREATE TABLE IF NOT EXISTS revision (
+    id INTEGER PRIMARY KEY NOT NULL,
+    forecastId INTEGER NOT NULL,
+    createdAt DATETIME NOT NULL,
+    note TEXT,
+    data JSON NOT NULL,
+    FOREIGN KEY (forecastId) REFERENCES forecast(id)
+);
+
+CREATE INDEX IF NOT EXISTS revisionForecastId ON revision (forecastId, createdAt);
+
+INSERT INTO revision (forecastId, createdAt, data)
+SELECT id, CURRENT_TIMESTAMP, data
+FROM forecast
+WHERE data IS NOT NULL;
+
+ALTER TABLE forecast DROP COLUMN data;
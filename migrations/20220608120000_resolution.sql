@@ -0,0 +1,4 @@
+-- A forecast is resolved once resolvedAt is set. outcomeDate is the date the
+-- event actually happened, or NULL if it didn't happen.
+ALTER TABLE forecast ADD COLUMN resolvedAt DATETIME;
+ALTER TABLE forecast ADD COLUMN outcomeDate DATE;
@@ -0,0 +1,6 @@
+-- Scores for a resolved forecast, recalculated whenever it is resolved. They
+-- are NULL if the forecast hasn't been resolved, or the outcome wasn't in any
+-- of its ranges.
+ALTER TABLE forecast ADD COLUMN logScore REAL;
+ALTER TABLE forecast ADD COLUMN brierScore REAL;
+ALTER TABLE forecast ADD COLUMN rankedProbabilityScore REAL;
@@ -0,0 +1,5 @@
+-- Scores averaged over every revision of a resolved forecast, each weighted by
+-- how long it was the latest.
+ALTER TABLE forecast ADD COLUMN timeWeightedLogScore REAL;
+ALTER TABLE forecast ADD COLUMN timeWeightedBrierScore REAL;
+ALTER TABLE forecast ADD COLUMN timeWeightedRankedProbabilityScore REAL;
@@ -0,0 +1,5 @@
+-- Describes what a forecast is about. Tags are a JSON array of strings.
+ALTER TABLE forecast ADD COLUMN description TEXT;
+ALTER TABLE forecast ADD COLUMN criteria TEXT;
+ALTER TABLE forecast ADD COLUMN tags JSON;
+ALTER TABLE forecast ADD COLUMN owner TEXT;
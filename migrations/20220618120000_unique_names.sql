@@ -0,0 +1,7 @@
+-- Names used to be reused rather than rejected, so there may already be
+-- duplicates. Every one but the oldest gets its id added to its name.
+UPDATE forecast
+SET name = name || ' (' || id || ')'
+WHERE id NOT IN (SELECT MIN(id) FROM forecast GROUP BY name);
+
+CREATE UNIQUE INDEX forecastName ON forecast(name);
@@ -1,48 +1,234 @@
 use std::{env, str::FromStr};
 
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
 use sqlx::SqlitePool;
 use strum_macros::{Display, EnumString};
 
-use crate::forecasts::ui::range::Range;
+use crate::forecasts::distribution::Distribution;
+use crate::forecasts::ui::{numeric::NumericRange, outcome::Outcome, range::Range};
+
+// The ways the database can refuse to do something, which callers may want to
+// tell apart. Anything else, like losing the connection, is `Other`.
+#[derive(Debug)]
+pub enum DbError {
+    NoForecast(i64),
+    NameTaken(String),
+    // Resolved forecasts are locked.
+    Resolved(i64),
+    Other(anyhow::Error),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DbError::NoForecast(id) => write!(f, "No forecast with id {}", id),
+            DbError::NameTaken(name) => {
+                write!(f, "There is already a forecast called '{}'", name)
+            }
+            DbError::Resolved(id) => write!(
+                f,
+                "Forecast {} has been resolved, so it can't be changed",
+                id
+            ),
+            DbError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<anyhow::Error> for DbError {
+    fn from(e: anyhow::Error) -> DbError {
+        DbError::Other(e)
+    }
+}
+
+impl From<sqlx::Error> for DbError {
+    fn from(e: sqlx::Error) -> DbError {
+        DbError::Other(e.into())
+    }
+}
 
 pub struct NewForecast {
     pub name: String,
     pub forecast_type: ForecastType,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, EnumString, Display)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, EnumString, Display, JsonSchema)]
 pub enum ForecastType {
     Date,
+    Numeric,
+    Binary,
+    MultipleChoice,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Debug, PartialEq, JsonSchema)]
 pub struct SavedForecast {
     pub id: i64,
     pub name: String,
     pub forecast_type: ForecastType,
-    pub data: Option<RangeForecast>,
+    pub data: Option<ForecastData>,
+    pub resolution: Option<Resolution>,
+    pub metadata: ForecastMetadata,
+}
+
+// Everything about a forecast bar its name that the user can edit.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default, JsonSchema)]
+#[serde(default)]
+pub struct ForecastMetadata {
+    pub description: Option<String>,
+    // How we'll decide what actually happened.
+    pub criteria: Option<String>,
+    pub tags: Vec<String>,
+    pub owner: Option<String>,
+}
+
+impl ForecastMetadata {
+    fn from_columns(
+        description: Option<String>,
+        criteria: Option<String>,
+        tags: Option<Json<Vec<String>>>,
+        owner: Option<String>,
+    ) -> ForecastMetadata {
+        ForecastMetadata {
+            description,
+            criteria,
+            tags: tags.map(|tags| tags.0).unwrap_or_default(),
+            owner,
+        }
+    }
+}
+
+// What actually happened. An outcome date of None means it didn't happen.
+#[derive(Serialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct Resolution {
+    pub resolved_at: NaiveDateTime,
+    pub outcome_date: Option<NaiveDate>,
+    // The scores of the forecast as it was when resolved.
+    pub scores: Option<Scores>,
+    // The scores of every revision, averaged over the life of the forecast.
+    pub time_weighted_scores: Option<Scores>,
+}
+
+impl Resolution {
+    fn from_columns(
+        resolved_at: Option<NaiveDateTime>,
+        outcome_date: Option<NaiveDate>,
+        scores: Option<Scores>,
+        time_weighted_scores: Option<Scores>,
+    ) -> Option<Resolution> {
+        resolved_at.map(|resolved_at| Resolution {
+            resolved_at,
+            outcome_date,
+            scores,
+            time_weighted_scores,
+        })
+    }
+}
+
+// How well a resolved forecast did. See forecasts::score for how each is
+// calculated.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy, JsonSchema)]
+pub struct Scores {
+    // None when the outcome's range was given nothing, which makes the log
+    // score -inf: JSON can't carry that.
+    pub log: Option<f64>,
+    pub brier: f64,
+    pub ranked_probability: f64,
+}
+
+impl Scores {
+    fn from_columns(
+        log: Option<f64>,
+        brier: Option<f64>,
+        ranked_probability: Option<f64>,
+    ) -> Option<Scores> {
+        Some(Scores {
+            log,
+            brier: brier?,
+            ranked_probability: ranked_probability?,
+        })
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+// The shape of a forecast's `data` column depends on its type, so it's
+// decoded using the `forecastType` column rather than tagged in the JSON.
+#[derive(Serialize, Debug, PartialEq, JsonSchema)]
+#[serde(untagged)]
+pub enum ForecastData {
+    Date(RangeForecast),
+    Numeric(NumericForecast),
+    Binary(BinaryForecast),
+    MultipleChoice(MultipleChoiceForecast),
+}
+
+impl ForecastData {
+    pub fn from_json(
+        forecast_type: &ForecastType,
+        json: serde_json::Value,
+    ) -> anyhow::Result<ForecastData> {
+        Ok(match forecast_type {
+            ForecastType::Date => ForecastData::Date(serde_json::from_value(json)?),
+            ForecastType::Numeric => ForecastData::Numeric(serde_json::from_value(json)?),
+            ForecastType::Binary => ForecastData::Binary(serde_json::from_value(json)?),
+            ForecastType::MultipleChoice => {
+                ForecastData::MultipleChoice(serde_json::from_value(json)?)
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
 pub struct RangeForecast {
     pub start_date: Option<String>,
     pub end_date: Option<String>,
+    pub number_of_ranges: Option<usize>,
+    // The least each range may hold, as a percentage. Forecasts saved before
+    // there was one have none.
+    pub min_value: Option<f64>,
     pub ranges: Option<Vec<Range>>,
+    // What the values were worked out from, until they're next changed by
+    // hand.
+    pub distribution: Option<Distribution>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct NumericForecast {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub log_scale: Option<bool>,
+    pub units: Option<String>,
+    pub number_of_ranges: Option<usize>,
+    pub ranges: Option<Vec<NumericRange>>,
+}
+
+// A yes/no question: the probability, as a percentage, that it happens by a
+// given date.
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct BinaryForecast {
+    pub by_date: Option<String>,
+    pub probability: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, JsonSchema)]
+pub struct MultipleChoiceForecast {
+    pub outcomes: Option<Vec<Outcome>>,
 }
 
 impl std::fmt::Display for RangeForecast {
     // This trait requires `fmt` with this exact signature.
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if self.start_date.is_some() {
-            write!(f, "{}", self.start_date.as_ref().unwrap())?;
+        if let Some(start_date) = &self.start_date {
+            write!(f, "{}", start_date)?;
         }
-        if self.end_date.is_some() {
-            write!(f, "{}", self.end_date.as_ref().unwrap())?;
+        if let Some(end_date) = &self.end_date {
+            write!(f, "{}", end_date)?;
         }
-        if self.ranges.is_some() {
-            for range in self.ranges.as_ref().unwrap() {
+        if let Some(ranges) = &self.ranges {
+            for range in ranges {
                 write!(f, "{:#}", range)?;
             }
         }
@@ -53,6 +239,16 @@ impl std::fmt::Display for RangeForecast {
     }
 }
 
+// An immutable snapshot of a forecast's data, taken whenever it changes.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Revision {
+    pub id: i64,
+    pub forecast_id: i64,
+    pub created_at: NaiveDateTime,
+    pub note: Option<String>,
+    pub data: ForecastData,
+}
+
 impl SavedForecast {
     pub fn from(new_forecast: NewForecast, id: i64) -> SavedForecast {
         // This is a mapping from NewForecast to SavedForecast.
@@ -61,6 +257,8 @@ impl SavedForecast {
             name: new_forecast.name.clone(),
             forecast_type: new_forecast.forecast_type,
             data: None,
+            resolution: None,
+            metadata: ForecastMetadata::default(),
         }
     }
 }
@@ -85,10 +283,25 @@ impl Database {
         Ok(())
     }
 
-    pub async fn find(&self) -> anyhow::Result<Vec<SavedForecast>> {
+    pub async fn find(&self) -> Result<Vec<SavedForecast>, DbError> {
         let recs = sqlx::query!(
             r#"
-SELECT id, name, forecastType
+SELECT
+    id,
+    name,
+    forecastType,
+    resolvedAt AS "resolved_at: NaiveDateTime",
+    outcomeDate AS "outcome_date: NaiveDate",
+    logScore AS "log_score: f64",
+    brierScore AS "brier_score: f64",
+    rankedProbabilityScore AS "ranked_probability_score: f64",
+    timeWeightedLogScore AS "time_weighted_log_score: f64",
+    timeWeightedBrierScore AS "time_weighted_brier_score: f64",
+    timeWeightedRankedProbabilityScore AS "time_weighted_ranked_probability_score: f64",
+    description,
+    criteria,
+    tags AS "tags: Json<Vec<String>>",
+    owner
 FROM forecast
 ORDER BY id
         "#
@@ -100,15 +313,108 @@ ORDER BY id
             forecasts.push(SavedForecast {
                 id: rec.id,
                 name: rec.name,
-                forecast_type: ForecastType::from_str(&rec.forecastType)
-                    .expect("Invalid forecast type"),
+                forecast_type: parse_forecast_type(&rec.forecastType)?,
                 data: None,
+                resolution: Resolution::from_columns(
+                    rec.resolved_at,
+                    rec.outcome_date,
+                    Scores::from_columns(
+                        rec.log_score,
+                        rec.brier_score,
+                        rec.ranked_probability_score,
+                    ),
+                    Scores::from_columns(
+                        rec.time_weighted_log_score,
+                        rec.time_weighted_brier_score,
+                        rec.time_weighted_ranked_probability_score,
+                    ),
+                ),
+                metadata: ForecastMetadata::from_columns(
+                    rec.description,
+                    rec.criteria,
+                    rec.tags,
+                    rec.owner,
+                ),
             })
         }
         Ok(forecasts)
     }
 
-    pub async fn create(&self, forecast: NewForecast) -> anyhow::Result<SavedForecast> {
+    // Every resolved forecast with its latest revision, which is the one that
+    // was resolved, in one query.
+    pub async fn find_resolved(&self) -> Result<Vec<SavedForecast>, DbError> {
+        let recs = sqlx::query!(
+            r#"
+SELECT
+    forecast.id,
+    forecast.name,
+    forecast.forecastType,
+    forecast.resolvedAt AS "resolved_at?: NaiveDateTime",
+    forecast.outcomeDate AS "outcome_date?: NaiveDate",
+    forecast.logScore AS "log_score?: f64",
+    forecast.brierScore AS "brier_score?: f64",
+    forecast.rankedProbabilityScore AS "ranked_probability_score?: f64",
+    forecast.timeWeightedLogScore AS "time_weighted_log_score?: f64",
+    forecast.timeWeightedBrierScore AS "time_weighted_brier_score?: f64",
+    forecast.timeWeightedRankedProbabilityScore
+        AS "time_weighted_ranked_probability_score?: f64",
+    forecast.description,
+    forecast.criteria,
+    forecast.tags AS "tags?: Json<Vec<String>>",
+    forecast.owner,
+    revision.data AS "data?: Json<serde_json::Value>"
+FROM forecast
+LEFT JOIN revision ON revision.id = (
+    SELECT id
+    FROM revision
+    WHERE forecastId = forecast.id
+    ORDER BY createdAt DESC, id DESC
+    LIMIT 1
+)
+WHERE forecast.resolvedAt IS NOT NULL
+ORDER BY forecast.id
+        "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut forecasts = Vec::new();
+        for rec in recs {
+            let forecast_type = parse_forecast_type(&rec.forecastType)?;
+            let data = match rec.data {
+                Some(json) => Some(ForecastData::from_json(&forecast_type, json.0)?),
+                None => None,
+            };
+            forecasts.push(SavedForecast {
+                id: rec.id,
+                name: rec.name,
+                forecast_type,
+                data,
+                resolution: Resolution::from_columns(
+                    rec.resolved_at,
+                    rec.outcome_date,
+                    Scores::from_columns(
+                        rec.log_score,
+                        rec.brier_score,
+                        rec.ranked_probability_score,
+                    ),
+                    Scores::from_columns(
+                        rec.time_weighted_log_score,
+                        rec.time_weighted_brier_score,
+                        rec.time_weighted_ranked_probability_score,
+                    ),
+                ),
+                metadata: ForecastMetadata::from_columns(
+                    rec.description,
+                    rec.criteria,
+                    rec.tags,
+                    rec.owner,
+                ),
+            })
+        }
+        Ok(forecasts)
+    }
+
+    pub async fn create(&self, forecast: NewForecast) -> Result<SavedForecast, DbError> {
         let forecast_type = forecast.forecast_type.to_string();
         let id = sqlx::query!(
             r#"
@@ -119,15 +425,31 @@ VALUES (?1, ?2);
             forecast_type
         )
         .execute(&self.pool)
-        .await?
+        .await
+        .map_err(|e| name_taken_error(e, &forecast.name))?
         .last_insert_rowid();
         Ok(SavedForecast::from(forecast, id))
     }
 
-    pub async fn read_by_name(&self, name: String) -> Option<SavedForecast> {
+    pub async fn read_by_name(&self, name: String) -> Result<Option<SavedForecast>, DbError> {
         let rec = sqlx::query!(
             r#"
-SELECT id, name, forecastType
+SELECT
+    id,
+    name,
+    forecastType,
+    resolvedAt AS "resolved_at: NaiveDateTime",
+    outcomeDate AS "outcome_date: NaiveDate",
+    logScore AS "log_score: f64",
+    brierScore AS "brier_score: f64",
+    rankedProbabilityScore AS "ranked_probability_score: f64",
+    timeWeightedLogScore AS "time_weighted_log_score: f64",
+    timeWeightedBrierScore AS "time_weighted_brier_score: f64",
+    timeWeightedRankedProbabilityScore AS "time_weighted_ranked_probability_score: f64",
+    description,
+    criteria,
+    tags AS "tags: Json<Vec<String>>",
+    owner
 FROM forecast
 WHERE name = ?1
         "#,
@@ -137,30 +459,67 @@ WHERE name = ?1
         .await;
 
         match rec {
-            Ok(rec) => Some(SavedForecast {
+            Ok(rec) => Ok(Some(SavedForecast {
                 id: rec.id,
                 name: rec.name,
-                forecast_type: ForecastType::from_str(&rec.forecastType)
-                    .expect("Invalid forecast type"),
+                forecast_type: parse_forecast_type(&rec.forecastType)?,
                 data: None,
-            }),
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => None,
-                _ => panic!("{}", e),
-            },
+                resolution: Resolution::from_columns(
+                    rec.resolved_at,
+                    rec.outcome_date,
+                    Scores::from_columns(
+                        rec.log_score,
+                        rec.brier_score,
+                        rec.ranked_probability_score,
+                    ),
+                    Scores::from_columns(
+                        rec.time_weighted_log_score,
+                        rec.time_weighted_brier_score,
+                        rec.time_weighted_ranked_probability_score,
+                    ),
+                ),
+                metadata: ForecastMetadata::from_columns(
+                    rec.description,
+                    rec.criteria,
+                    rec.tags,
+                    rec.owner,
+                ),
+            })),
+            Err(sqlx::Error::RowNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub async fn read_by_id(&self, id: i64) -> Option<SavedForecast> {
+    pub async fn read_by_id(&self, id: i64) -> Result<Option<SavedForecast>, DbError> {
         let rec = sqlx::query!(
             r#"
 SELECT 
-    id, 
-    name, 
-    forecastType, 
-    data AS "data: Json<RangeForecast>"
+    forecast.id, 
+    forecast.name, 
+    forecast.forecastType, 
+    forecast.resolvedAt AS "resolved_at?: NaiveDateTime",
+    forecast.outcomeDate AS "outcome_date?: NaiveDate",
+    forecast.logScore AS "log_score?: f64",
+    forecast.brierScore AS "brier_score?: f64",
+    forecast.rankedProbabilityScore AS "ranked_probability_score?: f64",
+    forecast.timeWeightedLogScore AS "time_weighted_log_score?: f64",
+    forecast.timeWeightedBrierScore AS "time_weighted_brier_score?: f64",
+    forecast.timeWeightedRankedProbabilityScore
+        AS "time_weighted_ranked_probability_score?: f64",
+    forecast.description,
+    forecast.criteria,
+    forecast.tags AS "tags?: Json<Vec<String>>",
+    forecast.owner,
+    revision.data AS "data?: Json<serde_json::Value>"
 FROM forecast
-WHERE id = ?1
+LEFT JOIN revision ON revision.id = (
+    SELECT id
+    FROM revision
+    WHERE forecastId = forecast.id
+    ORDER BY createdAt DESC, id DESC
+    LIMIT 1
+)
+WHERE forecast.id = ?1
         "#,
             id
         )
@@ -169,77 +528,231 @@ WHERE id = ?1
 
         match rec {
             Ok(rec) => {
-                let data = if let Some(json) = rec.data {
-                    Some(json.0)
-                } else {
-                    None
+                let forecast_type = parse_forecast_type(&rec.forecastType)?;
+                let data = match rec.data {
+                    Some(json) => Some(ForecastData::from_json(&forecast_type, json.0)?),
+                    None => None,
                 };
-                Some(SavedForecast {
+                Ok(Some(SavedForecast {
                     id: rec.id,
                     name: rec.name,
-                    forecast_type: ForecastType::from_str(&rec.forecastType)
-                        .expect("Invalid forecast type"),
+                    forecast_type,
                     data,
-                })
+                    resolution: Resolution::from_columns(
+                        rec.resolved_at,
+                        rec.outcome_date,
+                        Scores::from_columns(
+                            rec.log_score,
+                            rec.brier_score,
+                            rec.ranked_probability_score,
+                        ),
+                        Scores::from_columns(
+                            rec.time_weighted_log_score,
+                            rec.time_weighted_brier_score,
+                            rec.time_weighted_ranked_probability_score,
+                        ),
+                    ),
+                    metadata: ForecastMetadata::from_columns(
+                        rec.description,
+                        rec.criteria,
+                        rec.tags,
+                        rec.owner,
+                    ),
+                }))
             }
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => None,
-                _ => panic!("bad {}", e),
+            Err(sqlx::Error::RowNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Forecast data is never changed in place. Each update is a new revision,
+    // and the latest revision is the forecast's current state. Resolved
+    // forecasts are locked, so can't be updated.
+    pub async fn update_data(
+        &self,
+        id: i64,
+        data: ForecastData,
+        note: Option<String>,
+    ) -> Result<(), DbError> {
+        let data_json = serde_json::to_string(&data).map_err(anyhow::Error::from)?;
+        let created_at = Utc::now().naive_utc();
+        let rec = sqlx::query!(
+            r#"
+INSERT INTO revision (forecastId, createdAt, note, data)
+SELECT id, ?1, ?2, ?3
+FROM forecast
+WHERE id = ?4 AND resolvedAt IS NULL
+        "#,
+            created_at,
+            note,
+            data_json,
+            id
+        )
+        .execute(&self.pool)
+        .await;
+
+        match rec {
+            Ok(result) if result.rows_affected() == 0 => match self.read_by_id(id).await? {
+                Some(_) => Err(DbError::Resolved(id)),
+                None => Err(DbError::NoForecast(id)),
             },
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn find_revisions(&self, forecast_id: i64) -> Result<Vec<Revision>, DbError> {
+        let forecast_type = match self.read_by_id(forecast_id).await? {
+            Some(forecast) => forecast.forecast_type,
+            None => return Err(DbError::NoForecast(forecast_id)),
+        };
+        let recs = sqlx::query!(
+            r#"
+SELECT
+    id,
+    forecastId,
+    createdAt AS "created_at: NaiveDateTime",
+    note,
+    data AS "data: Json<serde_json::Value>"
+FROM revision
+WHERE forecastId = ?1
+ORDER BY createdAt DESC, id DESC
+        "#,
+            forecast_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut revisions = Vec::new();
+        for rec in recs {
+            revisions.push(Revision {
+                id: rec.id,
+                forecast_id: rec.forecastId,
+                created_at: rec.created_at,
+                note: rec.note,
+                data: ForecastData::from_json(&forecast_type, rec.data.0)?,
+            })
         }
+        Ok(revisions)
     }
 
-    pub async fn update_data(&self, id: i64, data: RangeForecast) -> anyhow::Result<()> {
-        let data_json = serde_json::to_string(&data)?;
+    pub async fn read_revision(
+        &self,
+        forecast_id: i64,
+        revision_id: i64,
+    ) -> Result<Option<Revision>, DbError> {
+        Ok(self
+            .find_revisions(forecast_id)
+            .await?
+            .into_iter()
+            .find(|revision| revision.id == revision_id))
+    }
+
+    // The revision that was current at a point in time: the last one saved at
+    // or before it.
+    pub async fn read_revision_at(
+        &self,
+        forecast_id: i64,
+        at: NaiveDateTime,
+    ) -> Result<Option<Revision>, DbError> {
+        Ok(self
+            .find_revisions(forecast_id)
+            .await?
+            .into_iter()
+            .find(|revision| revision.created_at <= at))
+    }
+
+    // Resolving a forecast again corrects its resolution, replacing its scores.
+    // It keeps the time it was first resolved, which is when its ranges were
+    // locked.
+    pub async fn resolve(
+        &self,
+        id: i64,
+        outcome_date: Option<NaiveDate>,
+        scores: Option<Scores>,
+        time_weighted_scores: Option<Scores>,
+    ) -> Result<(), DbError> {
+        let resolved_at = Utc::now().naive_utc();
+        let log_score = scores.and_then(|scores| scores.log);
+        let brier_score = scores.map(|scores| scores.brier);
+        let ranked_probability_score = scores.map(|scores| scores.ranked_probability);
+        let time_weighted_log_score = time_weighted_scores.and_then(|scores| scores.log);
+        let time_weighted_brier_score = time_weighted_scores.map(|scores| scores.brier);
+        let time_weighted_ranked_probability_score =
+            time_weighted_scores.map(|scores| scores.ranked_probability);
         let rec = sqlx::query!(
             r#"
 UPDATE forecast
-SET data = ?1
-WHERE id =?2 
+SET
+    resolvedAt = COALESCE(resolvedAt, ?1),
+    outcomeDate = ?2,
+    logScore = ?3,
+    brierScore = ?4,
+    rankedProbabilityScore = ?5,
+    timeWeightedLogScore = ?6,
+    timeWeightedBrierScore = ?7,
+    timeWeightedRankedProbabilityScore = ?8
+WHERE id = ?9
         "#,
-            data_json,
+            resolved_at,
+            outcome_date,
+            log_score,
+            brier_score,
+            ranked_probability_score,
+            time_weighted_log_score,
+            time_weighted_brier_score,
+            time_weighted_ranked_probability_score,
             id
         )
         .execute(&self.pool)
         .await;
 
         match rec {
+            Ok(result) if result.rows_affected() == 0 => Err(DbError::NoForecast(id)),
             Ok(_) => Ok(()),
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => Err(anyhow::anyhow!(
-                    "Updating data but found no forecast with id {}",
-                    id
-                )),
-                _ => panic!("{}", e),
-            },
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub async fn update(&self, forecast: SavedForecast) -> anyhow::Result<()> {
+    // Updates the forecast's name and metadata. Its data is updated with
+    // update_data instead.
+    pub async fn update(&self, forecast: SavedForecast) -> Result<(), DbError> {
+        let metadata = forecast.metadata;
+        let tags = Json(metadata.tags);
         let rec = sqlx::query!(
             r#"
 UPDATE forecast
-SET name = ?1
-WHERE id = ?2;
+SET name = ?1, description = ?2, criteria = ?3, tags = ?4, owner = ?5
+WHERE id = ?6;
         "#,
             forecast.name,
+            metadata.description,
+            metadata.criteria,
+            tags,
+            metadata.owner,
             forecast.id
         )
-        .fetch_one(&self.pool)
+        .execute(&self.pool)
         .await;
 
         match rec {
+            Ok(result) if result.rows_affected() == 0 => Err(DbError::NoForecast(forecast.id)),
             Ok(_) => Ok(()),
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => {
-                    Err(anyhow::anyhow!("No forecast with id {}", forecast.id))
-                }
-                _ => panic!("{}", e),
-            },
+            Err(e) => Err(name_taken_error(e, &forecast.name)),
         }
     }
 
-    pub async fn delete(&self, id: i64) -> anyhow::Result<()> {
+    // Deletes the forecast along with its revisions.
+    pub async fn delete(&self, id: i64) -> Result<(), DbError> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+DELETE FROM revision
+WHERE forecastId = ?1;
+        "#,
+            id
+        )
+        .execute(&mut transaction)
+        .await?;
         let rec = sqlx::query!(
             r#"
 DELETE FROM forecast
@@ -247,15 +760,34 @@ WHERE id = ?1;
         "#,
             id
         )
-        .execute(&self.pool)
-        .await;
-
-        match rec {
-            Ok(_) => Ok(()),
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => Err(anyhow::anyhow!("No forecast with id {}", id)),
-                _ => panic!("{}", e),
-            },
+        .execute(&mut transaction)
+        .await?;
+        if rec.rows_affected() == 0 {
+            return Err(DbError::NoForecast(id));
         }
+        transaction.commit().await?;
+        Ok(())
     }
 }
+
+// Forecast names are unique, so saving one with a name that's already taken
+// fails.
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(e) => e.message().contains("UNIQUE constraint failed"),
+        _ => false,
+    }
+}
+
+fn name_taken_error(e: sqlx::Error, name: &str) -> DbError {
+    if is_unique_violation(&e) {
+        DbError::NameTaken(name.to_string())
+    } else {
+        e.into()
+    }
+}
+
+fn parse_forecast_type(forecast_type: &str) -> Result<ForecastType, DbError> {
+    ForecastType::from_str(forecast_type)
+        .map_err(|_| anyhow::anyhow!("Invalid forecast type {}", forecast_type).into())
+}
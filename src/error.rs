@@ -0,0 +1,170 @@
+use actix_web::{
+    dev::ServiceResponse,
+    http::{
+        header::{self, ContentType, HeaderValue},
+        StatusCode,
+    },
+    middleware::ErrorHandlerResponse,
+    HttpResponse, ResponseError,
+};
+use askama::Template;
+use log::{error, warn};
+
+use crate::{
+    db::DbError,
+    forecasts::api::{prefers_json, ApiError},
+};
+
+// Everything that can go wrong handling a request. Each kind has its own
+// status code. The user sees an error page, or for htmx requests just the
+// message, while the cause is logged.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    pub fn bad_request(message: impl ToString) -> AppError {
+        AppError::BadRequest(message.to_string())
+    }
+
+    pub fn not_found(message: impl ToString) -> AppError {
+        AppError::NotFound(message.to_string())
+    }
+
+    pub fn conflict(message: impl ToString) -> AppError {
+        AppError::Conflict(message.to_string())
+    }
+
+    pub fn no_forecast(id: i64) -> AppError {
+        AppError::NotFound(format!("No forecast with id {}", id))
+    }
+
+    // Only internal errors are the server's fault, so only they are logged as
+    // errors.
+    pub fn log(&self) {
+        match self {
+            AppError::Internal(e) => error!("{:?}", e),
+            _ => warn!("{}", self),
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message) => write!(f, "{}", message),
+            // The cause is logged rather than shown, as it may give away more
+            // than it should.
+            AppError::Internal(_) => write!(f, "Something went wrong on our side"),
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> AppError {
+        AppError::Internal(e)
+    }
+}
+
+// The database doesn't know about status codes, so its errors are mapped here.
+impl From<DbError> for AppError {
+    fn from(e: DbError) -> AppError {
+        match e {
+            DbError::NoForecast(id) => AppError::no_forecast(id),
+            DbError::NameTaken(_) | DbError::Resolved(_) => AppError::conflict(e),
+            DbError::Other(e) => AppError::Internal(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> AppError {
+        AppError::Internal(e.into())
+    }
+}
+
+impl From<askama::Error> for AppError {
+    fn from(e: askama::Error) -> AppError {
+        AppError::Internal(e.into())
+    }
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate<'a> {
+    status: StatusCode,
+    message: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "_error.html")]
+struct ErrorFragmentTemplate<'a> {
+    message: &'a str,
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.log();
+        let message = self.to_string();
+        let body = ErrorTemplate {
+            status: self.status_code(),
+            message: &message,
+        }
+        .render()
+        .unwrap_or(message);
+        HttpResponse::build(self.status_code())
+            .content_type(ContentType::html())
+            .body(body)
+    }
+}
+
+// Swaps the error page for an `ApiError` when the client asked for JSON, or
+// for just the message when the request came from htmx, which shows it in the
+// page that made the request.
+pub fn tailor_error<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let message = match res
+        .response()
+        .error()
+        .and_then(|e| e.as_error::<AppError>())
+    {
+        Some(e) => e.to_string(),
+        None => return Ok(ErrorHandlerResponse::Response(res.map_into_left_body())),
+    };
+    let (request, mut response) = res.into_parts();
+    let body = if prefers_json(&request) {
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        serde_json::to_string(&ApiError {
+            error: message.clone(),
+        })
+        .unwrap_or(message)
+    } else if request.headers().contains_key("HX-Request") {
+        ErrorFragmentTemplate { message: &message }
+            .render()
+            .unwrap_or(message)
+    } else {
+        let res = ServiceResponse::new(request, response);
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    };
+    let response = response.set_body(body).map_into_boxed_body();
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(request, response).map_into_right_body(),
+    ))
+}
@@ -0,0 +1,348 @@
+use actix_web::{
+    http::{
+        header::{Accept, Header},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        DbError, ForecastData, ForecastMetadata, ForecastType, NewForecast, RangeForecast,
+        SavedForecast,
+    },
+    error::AppError,
+    AppData,
+};
+
+use super::{
+    percentile::Percentiles,
+    ui::{
+        forecast::{validate_name, EditPath},
+        range::{
+            get_ranges, round_value, save_data, total_value, validate_bounds, validate_min_value,
+            validate_values, validate_window, RangeKind, SaveTo, TailRanges, DEFAULT_MIN_VALUE,
+        },
+    },
+};
+
+// The JSON API mirrors the pages, so it works on the same types. Every error
+// is returned as an `ApiError`.
+#[derive(Serialize, JsonSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+fn error_response(status: StatusCode, error: impl ToString) -> HttpResponse {
+    HttpResponse::build(status).json(ApiError {
+        error: error.to_string(),
+    })
+}
+
+// Fails with the same status a page would, but with an `ApiError` body.
+#[derive(Debug)]
+pub struct ApiFailure(AppError);
+
+impl std::fmt::Display for ApiFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<AppError> for ApiFailure {
+    fn from(e: AppError) -> Self {
+        ApiFailure(e)
+    }
+}
+
+impl From<DbError> for ApiFailure {
+    fn from(e: DbError) -> Self {
+        ApiFailure(e.into())
+    }
+}
+
+impl ResponseError for ApiFailure {
+    fn status_code(&self) -> StatusCode {
+        self.0.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.0.log();
+        error_response(self.status_code(), &self.0)
+    }
+}
+
+type Result<T> = std::result::Result<T, ApiFailure>;
+
+// A forecast as the API and the forecast's page answer with it, along with
+// what's read off its data.
+#[derive(Serialize, JsonSchema)]
+pub struct ForecastResponse {
+    #[serde(flatten)]
+    pub forecast: SavedForecast,
+    // Read off the ranges of a date forecast, so nobody has to work them out
+    // from the labels.
+    pub percentiles: Option<Percentiles>,
+}
+
+impl From<SavedForecast> for ForecastResponse {
+    fn from(forecast: SavedForecast) -> Self {
+        ForecastResponse {
+            percentiles: forecast.data.as_ref().and_then(Percentiles::from_data),
+            forecast,
+        }
+    }
+}
+
+// Extractors fail with plain text of their own, so the API's scope uses these
+// to fail with an `ApiError` instead.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|e, _| ApiFailure(AppError::bad_request(e)).into())
+}
+
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|e, _| ApiFailure(AppError::bad_request(e)).into())
+}
+
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|e, _| ApiFailure(AppError::bad_request(e)).into())
+}
+
+// Whether the client would rather have JSON than HTML, so pages can answer
+// scripts with the data they show.
+pub fn prefers_json(request: &HttpRequest) -> bool {
+    Accept::parse(request)
+        .map(|accept| accept.preference().essence_str() == "application/json")
+        .unwrap_or(false)
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateForecastRequest {
+    pub name: String,
+    // Defaults to a date forecast.
+    pub forecast_type: Option<ForecastType>,
+    #[serde(flatten)]
+    pub metadata: ForecastMetadata,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpdateForecastRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub metadata: ForecastMetadata,
+}
+
+// A date forecast's ranges, as sent to the API. Labels and the rest are worked
+// out from these, as they are for the ranges form. Only the ranges within the
+// forecast window need a start and end.
+#[derive(Deserialize, JsonSchema)]
+pub struct RangesRequest {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub ranges: Vec<RangeRequest>,
+    // The least each range may hold, as a percentage. Defaults to the
+    // forecast's current minimum, or 0.1% if it has no ranges yet.
+    pub min_value: Option<f64>,
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RangeRequest {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    #[serde(default)]
+    pub kind: RangeKind,
+    // A percentage, kept to a tenth of a percent.
+    pub value: f64,
+}
+
+impl RangesRequest {
+    // Tails may only come first and last, like they do in the ranges form.
+    fn range_bounds_and_tails(&self) -> anyhow::Result<(Vec<(NaiveDate, NaiveDate)>, TailRanges)> {
+        let last_index = self.ranges.len().saturating_sub(1);
+        let mut tails = TailRanges::default();
+        let mut range_bounds = Vec::new();
+        for (index, range) in self.ranges.iter().enumerate() {
+            match range.kind {
+                RangeKind::BeforeStart if index == 0 => tails.before_start = true,
+                RangeKind::AfterEnd if index == last_index => tails.after_end = true,
+                RangeKind::Within => match (range.start, range.end) {
+                    (Some(start), Some(end)) => range_bounds.push((start, end)),
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Range {} needs a start and an end",
+                            index + 1
+                        ))
+                    }
+                },
+                kind => {
+                    return Err(anyhow::anyhow!(
+                        "Range {} is {}, which can only be the {} range",
+                        index + 1,
+                        kind,
+                        if kind == RangeKind::BeforeStart {
+                            "first"
+                        } else {
+                            "last"
+                        }
+                    ))
+                }
+            }
+        }
+        Ok((range_bounds, tails))
+    }
+}
+
+// Lists every forecast, without its data. Read a forecast to get that.
+pub async fn list_forecasts(app_data: web::Data<AppData>) -> Result<HttpResponse> {
+    let forecasts = app_data.database.find().await?;
+    Ok(HttpResponse::Ok().json(forecasts))
+}
+
+pub async fn create_forecast(
+    request: web::Json<CreateForecastRequest>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let request = request.into_inner();
+    let database = &app_data.database;
+    let name = request.name.trim();
+    validate_name(name).map_err(AppError::bad_request)?;
+    let new_forecast = NewForecast {
+        name: name.to_string(),
+        forecast_type: request.forecast_type.unwrap_or(ForecastType::Date),
+    };
+    let mut forecast = database.create(new_forecast).await?;
+    if request.metadata != ForecastMetadata::default() {
+        forecast.metadata = request.metadata;
+        let id = forecast.id;
+        database.update(forecast).await?;
+        forecast = database
+            .read_by_id(id)
+            .await?
+            .ok_or_else(|| AppError::no_forecast(id))?;
+    }
+    Ok(HttpResponse::Created()
+        .append_header(("location", format!("/api/v1/forecasts/{}", forecast.id)))
+        .json(forecast))
+}
+
+pub async fn read_forecast(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    match app_data.database.read_by_id(path.id).await? {
+        Some(forecast) => Ok(HttpResponse::Ok().json(ForecastResponse::from(forecast))),
+        None => Err(AppError::no_forecast(path.id).into()),
+    }
+}
+
+// Replaces the forecast's name and metadata. Its ranges are updated separately.
+pub async fn update_forecast(
+    path: web::Path<EditPath>,
+    request: web::Json<UpdateForecastRequest>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let request = request.into_inner();
+    let database = &app_data.database;
+    let mut forecast = database
+        .read_by_id(path.id)
+        .await?
+        .ok_or_else(|| AppError::no_forecast(path.id))?;
+    let name = request.name.trim();
+    validate_name(name).map_err(AppError::bad_request)?;
+    forecast.name = name.to_string();
+    forecast.metadata = request.metadata;
+    database.update(forecast).await?;
+    read_forecast(path, app_data).await
+}
+
+pub async fn delete_forecast(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    app_data.database.delete(path.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn read_ranges(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    match app_data.database.read_by_id(path.id).await? {
+        Some(forecast) => match forecast.data {
+            Some(ForecastData::Date(range_forecast)) => Ok(HttpResponse::Ok().json(range_forecast)),
+            _ => {
+                Err(AppError::not_found(format!("Forecast {} has no date ranges", path.id)).into())
+            }
+        },
+        None => Err(AppError::no_forecast(path.id).into()),
+    }
+}
+
+// Saves a new revision of a date forecast's ranges. They must tile the
+// forecast window and add up to 100, as they must on the page.
+pub async fn update_ranges(
+    path: web::Path<EditPath>,
+    request: web::Json<RangesRequest>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse> {
+    let request = request.into_inner();
+    let database = &app_data.database;
+    let min_value = match database.read_by_id(path.id).await? {
+        Some(forecast) if forecast.resolution.is_some() => {
+            return Err(AppError::conflict(format!(
+                "Forecast {} has been resolved, so it can't be changed",
+                path.id
+            ))
+            .into());
+        }
+        Some(forecast) if forecast.forecast_type == ForecastType::Date => {
+            let current_min_value = match forecast.data {
+                Some(ForecastData::Date(range_forecast)) => range_forecast.min_value.unwrap_or(0.0),
+                _ => DEFAULT_MIN_VALUE,
+            };
+            request.min_value.unwrap_or(current_min_value)
+        }
+        Some(_) => {
+            return Err(AppError::bad_request(format!(
+                "Forecast {} is not a date forecast",
+                path.id
+            ))
+            .into());
+        }
+        None => return Err(AppError::no_forecast(path.id).into()),
+    };
+    let (range_bounds, tails) = request
+        .range_bounds_and_tails()
+        .map_err(AppError::bad_request)?;
+    validate_window(request.start_date, request.end_date, range_bounds.len())
+        .and_then(|()| validate_bounds(request.start_date, request.end_date, &range_bounds))
+        .map_err(AppError::bad_request)?;
+    let range_values: Vec<f64> = request
+        .ranges
+        .iter()
+        .map(|range| round_value(range.value))
+        .collect();
+    validate_min_value(min_value, range_values.len())
+        .and_then(|()| validate_values(&range_values, min_value))
+        .map_err(AppError::bad_request)?;
+    let range_forecast = RangeForecast {
+        start_date: Some(request.start_date.to_string()),
+        end_date: Some(request.end_date.to_string()),
+        number_of_ranges: Some(range_bounds.len()),
+        min_value: Some(min_value),
+        ranges: Some(get_ranges(&range_bounds, &range_values, tails, min_value)),
+        distribution: None,
+    };
+    let save_to = SaveTo {
+        database,
+        id: path.id,
+        note: request.note,
+    };
+    let total = total_value(range_values.iter().copied());
+    save_data(&save_to, total, ForecastData::Date(range_forecast)).await?;
+    read_ranges(path, app_data).await
+}
@@ -0,0 +1,418 @@
+use std::f64::consts::SQRT_2;
+
+use chrono::NaiveDate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ui::range::{normalized_values, Range, RangeKind};
+
+// The 90th percentile of the standard normal distribution, so an 80% interval
+// runs from this many standard deviations below the median to as many above.
+const Z_90: f64 = 1.281_551_565_544_600_4;
+
+// A description of when the event will happen, which the ranges' values can
+// be worked out from. Dates cover the whole day, so a date's middle is half a
+// day after it starts.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+#[serde(tag = "shape")]
+pub enum Distribution {
+    // A 10% chance it's before `low` and a 10% chance it's after `high`. The
+    // two sides of the median needn't be the same width.
+    Normal {
+        low: NaiveDate,
+        median: NaiveDate,
+        high: NaiveDate,
+    },
+    // Skewed later, counting from the start of the forecast window, with a 10%
+    // chance it's after `high`. It never happens before the window starts.
+    Lognormal {
+        median: NaiveDate,
+        high: NaiveDate,
+    },
+    // Certainly between `low` and `high`, and most likely around `mode`.
+    Triangular {
+        low: NaiveDate,
+        mode: NaiveDate,
+        high: NaiveDate,
+    },
+}
+
+impl Distribution {
+    pub fn shape(&self) -> &'static str {
+        match self {
+            Distribution::Normal { .. } => "Normal",
+            Distribution::Lognormal { .. } => "Lognormal",
+            Distribution::Triangular { .. } => "Triangular",
+        }
+    }
+
+    pub fn low(&self) -> Option<NaiveDate> {
+        match self {
+            Distribution::Normal { low, .. } | Distribution::Triangular { low, .. } => Some(*low),
+            Distribution::Lognormal { .. } => None,
+        }
+    }
+
+    pub fn middle(&self) -> NaiveDate {
+        match self {
+            Distribution::Normal { median, .. } | Distribution::Lognormal { median, .. } => *median,
+            Distribution::Triangular { mode, .. } => *mode,
+        }
+    }
+
+    pub fn high(&self) -> NaiveDate {
+        match self {
+            Distribution::Normal { high, .. }
+            | Distribution::Lognormal { high, .. }
+            | Distribution::Triangular { high, .. } => *high,
+        }
+    }
+
+    // The dates must be in order, and far enough apart to give the shape some
+    // width.
+    pub fn validate(&self, start_date: NaiveDate) -> anyhow::Result<()> {
+        match self {
+            Distribution::Normal { low, median, high } => {
+                if !(low < median && median < high) {
+                    return Err(anyhow::anyhow!(
+                        "The 10th percentile, median and 90th percentile must be on different days, in that order"
+                    ));
+                }
+            }
+            Distribution::Lognormal { median, high } => {
+                if *median < start_date {
+                    return Err(anyhow::anyhow!(
+                        "A lognormal distribution counts from the forecast's start date, {}, so its median can't be before it",
+                        start_date
+                    ));
+                }
+                if high <= median {
+                    return Err(anyhow::anyhow!(
+                        "The 90th percentile must be after the median"
+                    ));
+                }
+            }
+            Distribution::Triangular { low, mode, high } => {
+                if !(low <= mode && mode <= high) {
+                    return Err(anyhow::anyhow!(
+                        "The most likely date must be between the earliest and the latest"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The chance it has happened by `days` after the start of the window.
+    fn cdf(&self, start_date: NaiveDate, days: f64) -> f64 {
+        let start_of = |date: &NaiveDate| (*date - start_date).num_days() as f64;
+        let middle_of = |date: &NaiveDate| start_of(date) + 0.5;
+        match self {
+            Distribution::Normal { low, median, high } => {
+                let median = middle_of(median);
+                let width = if days < median {
+                    median - middle_of(low)
+                } else {
+                    middle_of(high) - median
+                };
+                standard_normal_cdf((days - median) * Z_90 / width)
+            }
+            Distribution::Lognormal { median, high } => {
+                if days <= 0.0 {
+                    return 0.0;
+                }
+                let mu = middle_of(median).ln();
+                let sigma = (middle_of(high).ln() - mu) / Z_90;
+                standard_normal_cdf((days.ln() - mu) / sigma)
+            }
+            Distribution::Triangular { low, mode, high } => {
+                let (low, mode, high) = (start_of(low), middle_of(mode), start_of(high) + 1.0);
+                if days <= low {
+                    0.0
+                } else if days >= high {
+                    1.0
+                } else if days <= mode {
+                    (days - low).powi(2) / ((high - low) * (mode - low))
+                } else {
+                    1.0 - (high - days).powi(2) / ((high - low) * (high - mode))
+                }
+            }
+        }
+    }
+
+    // Shares 100% between the ranges by how likely the distribution says each
+    // is. Whatever falls outside the ranges, when there are no tails to catch
+    // it, is shared out in proportion, and every range keeps the minimum. A
+    // distribution that doesn't validate has no sensible values.
+    pub fn range_values(
+        &self,
+        start_date: NaiveDate,
+        ranges: &[Range],
+        min_value: f64,
+    ) -> anyhow::Result<Vec<f64>> {
+        self.validate(start_date)?;
+        let days = |date: NaiveDate| (date - start_date).num_days() as f64;
+        let probabilities: Vec<f64> = ranges
+            .iter()
+            .map(|range| match range.kind {
+                RangeKind::BeforeStart => self.cdf(start_date, days(range.end) + 1.0),
+                RangeKind::Within => {
+                    self.cdf(start_date, days(range.end) + 1.0)
+                        - self.cdf(start_date, days(range.start))
+                }
+                RangeKind::AfterEnd => 1.0 - self.cdf(start_date, days(range.start)),
+            })
+            .collect();
+        let total: f64 = probabilities.iter().sum();
+        if total < 0.001 {
+            return Err(anyhow::anyhow!(
+                "That distribution gives next to no chance to any of the ranges"
+            ));
+        }
+        let values: Vec<f64> = probabilities
+            .iter()
+            .map(|probability| probability * 100.0 / total)
+            .collect();
+        Ok(normalized_values(&values, min_value))
+    }
+}
+
+fn standard_normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / SQRT_2))
+}
+
+// Abramowitz and Stegun's approximation 7.1.26. It's good to about 1e-7,
+// which is plenty for tenths of a percent.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let y = 1.0 - polynomial * (-x * x).exp();
+    y.copysign(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecasts::ui::range::{equal_bounds, get_ranges, TailRanges};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2022, 6, day)
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    // Three ranges over June, with or without a tail either side.
+    fn june_ranges(with_tails: bool) -> Vec<Range> {
+        let tails = TailRanges {
+            before_start: with_tails,
+            after_end: with_tails,
+        };
+        let number_of_values = 3 + tails.count();
+        get_ranges(
+            &equal_bounds(date(1), date(30), 3),
+            &vec![0.0; number_of_values],
+            tails,
+            0.0,
+        )
+    }
+
+    fn assert_shares_100(values: &[f64], min_value: f64) {
+        let tenths: i64 = values
+            .iter()
+            .map(|value| (value * 10.0).round() as i64)
+            .sum();
+        assert_eq!(tenths, 1000, "{:?}", values);
+        assert!(
+            values.iter().all(|value| *value >= min_value),
+            "{:?}",
+            values
+        );
+    }
+
+    #[test]
+    fn erf_matches_known_values() {
+        for (x, expected) in [
+            (0.0, 0.0),
+            (0.5, 0.520_499_877_8),
+            (1.0, 0.842_700_792_9),
+            (2.0, 0.995_322_265_0),
+            (-1.0, -0.842_700_792_9),
+        ] {
+            assert_close(erf(x), expected, 1.5e-7);
+        }
+        assert_close(standard_normal_cdf(Z_90), 0.9, 1e-6);
+        assert_close(standard_normal_cdf(-Z_90), 0.1, 1e-6);
+    }
+
+    #[test]
+    fn normal_cdf_hits_its_percentiles() {
+        let distribution = Distribution::Normal {
+            low: date(5),
+            median: date(15),
+            high: date(28),
+        };
+        // Each date is measured at its middle.
+        assert_close(distribution.cdf(date(1), 4.5), 0.1, 1e-6);
+        assert_close(distribution.cdf(date(1), 14.5), 0.5, 1e-6);
+        assert_close(distribution.cdf(date(1), 27.5), 0.9, 1e-6);
+    }
+
+    #[test]
+    fn lognormal_cdf_hits_its_percentiles_and_starts_at_zero() {
+        let distribution = Distribution::Lognormal {
+            median: date(10),
+            high: date(25),
+        };
+        assert_close(distribution.cdf(date(1), 9.5), 0.5, 1e-6);
+        assert_close(distribution.cdf(date(1), 24.5), 0.9, 1e-6);
+        assert_eq!(distribution.cdf(date(1), 0.0), 0.0);
+        assert_eq!(distribution.cdf(date(1), -3.0), 0.0);
+    }
+
+    #[test]
+    fn triangular_cdf_runs_from_low_to_high() {
+        let distribution = Distribution::Triangular {
+            low: date(1),
+            mode: date(5),
+            high: date(30),
+        };
+        assert_eq!(distribution.cdf(date(1), 0.0), 0.0);
+        assert_close(distribution.cdf(date(1), 4.5), 4.5 / 30.0, 1e-9);
+        assert_close(distribution.cdf(date(1), 15.0), 1.0 - 225.0 / 765.0, 1e-9);
+        assert_eq!(distribution.cdf(date(1), 30.0), 1.0);
+    }
+
+    #[test]
+    fn range_values_share_100_between_the_ranges() {
+        let distributions = [
+            Distribution::Normal {
+                low: date(5),
+                median: date(15),
+                high: date(28),
+            },
+            Distribution::Lognormal {
+                median: date(10),
+                high: date(25),
+            },
+            Distribution::Triangular {
+                low: date(1),
+                mode: date(5),
+                high: date(30),
+            },
+        ];
+        for distribution in &distributions {
+            for with_tails in [true, false] {
+                let values = distribution
+                    .range_values(date(1), &june_ranges(with_tails), 0.1)
+                    .unwrap();
+                assert_shares_100(&values, 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn a_symmetric_normal_gives_symmetric_values() {
+        // June 14 is the middle of three nine day ranges from June 1.
+        let distribution = Distribution::Normal {
+            low: date(4),
+            median: date(14),
+            high: date(24),
+        };
+        let tails = TailRanges {
+            before_start: true,
+            after_end: true,
+        };
+        let ranges = get_ranges(&equal_bounds(date(1), date(27), 3), &[0.0; 5], tails, 0.0);
+        let values = distribution.range_values(date(1), &ranges, 0.0).unwrap();
+        assert!(values[2] > values[1], "{:?}", values);
+        assert_close(values[0], values[4], 0.11);
+        assert_close(values[1], values[3], 0.11);
+    }
+
+    #[test]
+    fn a_normal_with_no_spread_is_rejected() {
+        let distribution = Distribution::Normal {
+            low: date(15),
+            median: date(15),
+            high: date(28),
+        };
+        assert!(distribution
+            .range_values(date(1), &june_ranges(true), 0.1)
+            .is_err());
+    }
+
+    #[test]
+    fn a_lognormal_must_start_within_the_window() {
+        let before_start = Distribution::Lognormal {
+            median: NaiveDate::from_ymd(2022, 5, 20),
+            high: date(25),
+        };
+        assert!(before_start
+            .range_values(date(1), &june_ranges(true), 0.1)
+            .is_err());
+        let no_spread = Distribution::Lognormal {
+            median: date(10),
+            high: date(10),
+        };
+        assert!(no_spread
+            .range_values(date(1), &june_ranges(true), 0.1)
+            .is_err());
+        let on_the_start = Distribution::Lognormal {
+            median: date(1),
+            high: date(25),
+        };
+        let values = on_the_start
+            .range_values(date(1), &june_ranges(true), 0.1)
+            .unwrap();
+        assert_shares_100(&values, 0.1);
+        // Nothing happens before the window starts, bar the minimum.
+        assert_eq!(values[0], 0.1);
+    }
+
+    #[test]
+    fn a_triangle_can_peak_at_either_edge() {
+        for (low, mode, high) in [(1, 1, 30), (1, 30, 30), (12, 12, 12)] {
+            let distribution = Distribution::Triangular {
+                low: date(low),
+                mode: date(mode),
+                high: date(high),
+            };
+            let values = distribution
+                .range_values(date(1), &june_ranges(true), 0.1)
+                .unwrap();
+            assert_shares_100(&values, 0.1);
+        }
+        // All on one day, so all in that day's range.
+        let one_day = Distribution::Triangular {
+            low: date(12),
+            mode: date(12),
+            high: date(12),
+        };
+        let values = one_day
+            .range_values(date(1), &june_ranges(false), 0.1)
+            .unwrap();
+        assert_eq!(values, vec![0.1, 99.8, 0.1]);
+    }
+
+    #[test]
+    fn a_distribution_missing_every_range_is_rejected() {
+        let distribution = Distribution::Normal {
+            low: NaiveDate::from_ymd(2030, 1, 1),
+            median: NaiveDate::from_ymd(2030, 2, 1),
+            high: NaiveDate::from_ymd(2030, 3, 1),
+        };
+        assert!(distribution
+            .range_values(date(1), &june_ranges(false), 0.1)
+            .is_err());
+    }
+}
@@ -1,2 +1,7 @@
+pub mod api;
+pub mod distribution;
+pub mod openapi;
+pub mod percentile;
 pub mod routes;
+pub mod score;
 pub mod ui;
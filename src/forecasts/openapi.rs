@@ -0,0 +1,130 @@
+use actix_web::{HttpResponse, Result};
+use schemars::{gen::SchemaGenerator, gen::SchemaSettings, JsonSchema};
+use serde_json::{json, Value};
+
+use crate::db::{RangeForecast, SavedForecast};
+
+use super::api::{
+    ApiError, CreateForecastRequest, ForecastResponse, RangesRequest, UpdateForecastRequest,
+};
+
+// Describes the JSON API as an OpenAPI 3 document. The schemas are generated
+// from the same Rust types the API uses, so only the paths are written here.
+pub fn openapi() -> Value {
+    let mut generator = SchemaGenerator::new(SchemaSettings::openapi3());
+    let forecast = schema_ref::<SavedForecast>(&mut generator);
+    let forecast_response = schema_ref::<ForecastResponse>(&mut generator);
+    let create_request = schema_ref::<CreateForecastRequest>(&mut generator);
+    let update_request = schema_ref::<UpdateForecastRequest>(&mut generator);
+    let ranges = schema_ref::<RangeForecast>(&mut generator);
+    let ranges_request = schema_ref::<RangesRequest>(&mut generator);
+    let error = schema_ref::<ApiError>(&mut generator);
+    let schemas = serde_json::to_value(generator.definitions()).expect("Schemas should serialize");
+
+    let id = json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "int64" }
+    });
+    let error_response =
+        |description: &str| json!({ "description": description, "content": json_content(&error) });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Forecaster",
+            "version": "1"
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": {
+            "/forecasts": {
+                "get": {
+                    "operationId": "listForecasts",
+                    "summary": "List all forecasts, without their data",
+                    "responses": {
+                        "200": {
+                            "description": "Every forecast",
+                            "content": json_content(&json!({ "type": "array", "items": forecast }))
+                        }
+                    }
+                },
+                "post": {
+                    "operationId": "createForecast",
+                    "summary": "Create a forecast",
+                    "requestBody": { "required": true, "content": json_content(&create_request) },
+                    "responses": {
+                        "201": { "description": "The new forecast", "content": json_content(&forecast) },
+                        "400": error_response("The name isn't valid"),
+                        "409": error_response("There is already a forecast with that name")
+                    }
+                }
+            },
+            "/forecasts/{id}": {
+                "parameters": [id],
+                "get": {
+                    "operationId": "readForecast",
+                    "summary": "Read a forecast, including its data",
+                    "responses": {
+                        "200": { "description": "The forecast", "content": json_content(&forecast_response) },
+                        "404": error_response("There is no forecast with that id")
+                    }
+                },
+                "put": {
+                    "operationId": "updateForecast",
+                    "summary": "Replace a forecast's name and details",
+                    "requestBody": { "required": true, "content": json_content(&update_request) },
+                    "responses": {
+                        "200": { "description": "The updated forecast", "content": json_content(&forecast_response) },
+                        "400": error_response("The name isn't valid"),
+                        "404": error_response("There is no forecast with that id"),
+                        "409": error_response("There is already a forecast with that name")
+                    }
+                },
+                "delete": {
+                    "operationId": "deleteForecast",
+                    "summary": "Delete a forecast along with its revisions",
+                    "responses": {
+                        "204": { "description": "The forecast was deleted" },
+                        "404": error_response("There is no forecast with that id")
+                    }
+                }
+            },
+            "/forecasts/{id}/ranges": {
+                "parameters": [id],
+                "get": {
+                    "operationId": "readRanges",
+                    "summary": "Read a date forecast's ranges",
+                    "responses": {
+                        "200": { "description": "The ranges", "content": json_content(&ranges) },
+                        "404": error_response("There is no date forecast with ranges with that id")
+                    }
+                },
+                "put": {
+                    "operationId": "updateRanges",
+                    "summary": "Save new ranges for a date forecast",
+                    "requestBody": { "required": true, "content": json_content(&ranges_request) },
+                    "responses": {
+                        "200": { "description": "The saved ranges", "content": json_content(&ranges) },
+                        "400": error_response("The ranges aren't valid"),
+                        "404": error_response("There is no forecast with that id"),
+                        "409": error_response("The forecast has been resolved")
+                    }
+                }
+            }
+        },
+        "components": { "schemas": schemas }
+    })
+}
+
+fn schema_ref<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    serde_json::to_value(generator.subschema_for::<T>()).expect("Schemas should serialize")
+}
+
+fn json_content(schema: &Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+pub async fn openapi_json() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(openapi()))
+}
@@ -0,0 +1,178 @@
+use chrono::{Duration, NaiveDate};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::db::ForecastData;
+
+use super::ui::range::{Range, RangeKind};
+
+// The dates by which a date forecast gives the event a 10%, 50% and 90%
+// chance of having happened.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy, JsonSchema)]
+pub struct Percentiles {
+    pub p10: Percentile,
+    pub median: Percentile,
+    pub p90: Percentile,
+}
+
+// A percentile that falls in one of the open-ended ranges either side of the
+// window can't be pinned to a day, so it's given as the edge of the window it
+// falls beyond.
+#[derive(Serialize, Debug, PartialEq, Clone, Copy, JsonSchema)]
+pub struct Percentile {
+    pub kind: RangeKind,
+    pub date: NaiveDate,
+}
+
+impl std::fmt::Display for Percentile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.kind {
+            RangeKind::BeforeStart => write!(f, "Before {}", self.date),
+            RangeKind::Within => write!(f, "{}", self.date),
+            RangeKind::AfterEnd => write!(f, "After {}, or never", self.date),
+        }
+    }
+}
+
+impl Percentiles {
+    pub fn from_ranges(ranges: &[Range]) -> Option<Percentiles> {
+        Some(Percentiles {
+            p10: percentile(ranges, 0.1)?,
+            median: percentile(ranges, 0.5)?,
+            p90: percentile(ranges, 0.9)?,
+        })
+    }
+
+    // Only date forecasts with ranges have percentiles.
+    pub fn from_data(data: &ForecastData) -> Option<Percentiles> {
+        match data {
+            ForecastData::Date(range_forecast) => {
+                Percentiles::from_ranges(range_forecast.ranges.as_deref()?)
+            }
+            _ => None,
+        }
+    }
+}
+
+// The date by which `fraction` of the ranges' total value has been passed.
+// Each range's value is taken to be spread evenly over its days.
+pub fn percentile(ranges: &[Range], fraction: f64) -> Option<Percentile> {
+    let total: f64 = ranges.iter().map(|range| range.value).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let target = total * fraction;
+    let mut cumulative = 0.0;
+    for range in ranges.iter().filter(|range| range.value > 0.0) {
+        if cumulative + range.value >= target {
+            return percentile_within(range, (target - cumulative) / range.value);
+        }
+        cumulative += range.value;
+    }
+    // Adding up the values can leave the target just past the last of them.
+    let last = ranges.iter().rev().find(|range| range.value > 0.0)?;
+    percentile_within(last, 1.0)
+}
+
+// The day by the end of which `fraction` of a range has passed.
+fn percentile_within(range: &Range, fraction: f64) -> Option<Percentile> {
+    let date = match range.kind {
+        RangeKind::BeforeStart => range.end.succ_opt()?,
+        RangeKind::Within => {
+            let days = (range.end - range.start).num_days() + 1;
+            let offset = ((fraction * days as f64).ceil() as i64 - 1).clamp(0, days - 1);
+            range.start + Duration::days(offset)
+        }
+        RangeKind::AfterEnd => range.start.pred_opt()?,
+    };
+    Some(Percentile {
+        kind: range.kind,
+        date,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecasts::ui::range::{get_ranges, TailRanges};
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    // June 2022 in three ten-day ranges, with both tails.
+    fn june_ranges(values: &[f64]) -> Vec<Range> {
+        let range_bounds = vec![
+            (date("2022-06-01"), date("2022-06-10")),
+            (date("2022-06-11"), date("2022-06-20")),
+            (date("2022-06-21"), date("2022-06-30")),
+        ];
+        let tails = TailRanges {
+            before_start: true,
+            after_end: true,
+        };
+        get_ranges(&range_bounds, values, tails, 0.0)
+    }
+
+    fn within(value: &str) -> Percentile {
+        Percentile {
+            kind: RangeKind::Within,
+            date: date(value),
+        }
+    }
+
+    #[test]
+    fn percentiles_are_interpolated_within_ranges() {
+        let ranges = june_ranges(&[0.0, 20.0, 40.0, 40.0, 0.0]);
+        let percentiles = Percentiles::from_ranges(&ranges).unwrap();
+        // 10 of the first range's 20 has passed by the end of its fifth day.
+        assert_eq!(percentiles.p10, within("2022-06-05"));
+        // 30 of the second range's 40 is three quarters of the way through.
+        assert_eq!(percentiles.median, within("2022-06-18"));
+        // 30 of the last range's 40 is three quarters of the way through.
+        assert_eq!(percentiles.p90, within("2022-06-28"));
+    }
+
+    #[test]
+    fn a_percentile_in_a_tail_is_the_edge_of_the_window() {
+        let ranges = june_ranges(&[20.0, 20.0, 20.0, 15.0, 25.0]);
+        let percentiles = Percentiles::from_ranges(&ranges).unwrap();
+        assert_eq!(
+            percentiles.p10,
+            Percentile {
+                kind: RangeKind::BeforeStart,
+                date: date("2022-06-01"),
+            }
+        );
+        assert_eq!(percentiles.p10.to_string(), "Before 2022-06-01");
+        assert_eq!(percentiles.median, within("2022-06-15"));
+        assert_eq!(
+            percentiles.p90,
+            Percentile {
+                kind: RangeKind::AfterEnd,
+                date: date("2022-06-30"),
+            }
+        );
+        assert_eq!(percentiles.p90.to_string(), "After 2022-06-30, or never");
+    }
+
+    #[test]
+    fn a_percentile_on_a_boundary_is_the_last_day_of_the_earlier_range() {
+        let ranges = june_ranges(&[0.0, 50.0, 0.0, 50.0, 0.0]);
+        // The empty middle range is skipped over, not landed on.
+        assert_eq!(percentile(&ranges, 0.5), Some(within("2022-06-10")));
+    }
+
+    #[test]
+    fn a_target_just_past_the_total_is_the_end_of_the_last_range() {
+        let ranges = june_ranges(&[0.0, 50.0, 50.0, 0.0, 0.0]);
+        assert_eq!(percentile(&ranges, 1.0 + 1e-9), Some(within("2022-06-20")));
+    }
+
+    #[test]
+    fn no_percentiles_without_any_value() {
+        assert_eq!(Percentiles::from_ranges(&[]), None);
+        let ranges = june_ranges(&[0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(Percentiles::from_ranges(&ranges), None);
+    }
+}
@@ -1,36 +1,148 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use chrono::NaiveDate;
 use serde::Deserialize;
 
-use crate::AppData;
+use crate::{error::AppError, AppData};
 
-use super::ui::{forecast::EditPath, range::create_ranges};
+use super::ui::{
+    forecast::EditPath,
+    numeric::create_numeric_ranges,
+    outcome::create_outcomes,
+    range::{
+        bounds_from_end_dates, create_ranges, equal_bounds, round_value, validate_window,
+        TailRanges, DEFAULT_MIN_VALUE,
+    },
+};
 
 #[derive(Deserialize)]
 pub struct GenerateRangeQuery {
     start_date: NaiveDate,
     end_date: NaiveDate,
+    number_of_ranges: usize,
+    // Optional comma-separated last days of each range, bar the final one,
+    // for when the ranges shouldn't all be the same length.
+    range_end_dates: Option<String>,
+    // Checkboxes, so only present when ticked.
+    before_start: Option<String>,
+    after_end: Option<String>,
+    // The least each range may hold, as a percentage.
+    min_value: Option<f64>,
+}
+
+impl GenerateRangeQuery {
+    fn range_bounds(&self) -> anyhow::Result<Vec<(NaiveDate, NaiveDate)>> {
+        match self.range_end_dates.as_deref().map(str::trim) {
+            Some(range_end_dates) if !range_end_dates.is_empty() => {
+                let mut dates = Vec::new();
+                for date in range_end_dates.split(',') {
+                    dates.push(NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")?);
+                }
+                Ok(bounds_from_end_dates(
+                    self.start_date,
+                    self.end_date,
+                    &dates,
+                ))
+            }
+            _ => {
+                validate_window(self.start_date, self.end_date, self.number_of_ranges)?;
+                Ok(equal_bounds(
+                    self.start_date,
+                    self.end_date,
+                    self.number_of_ranges,
+                ))
+            }
+        }
+    }
+
+    fn tails(&self) -> TailRanges {
+        TailRanges {
+            before_start: self.before_start.is_some(),
+            after_end: self.after_end.is_some(),
+        }
+    }
 }
 
 pub async fn generate_ranges(
     path: web::Path<EditPath>,
     query: web::Query<GenerateRangeQuery>,
     app_data: web::Data<AppData>,
-) -> Result<HttpResponse> {
-    match create_ranges(
+) -> Result<HttpResponse, AppError> {
+    let range_bounds = query.range_bounds().map_err(AppError::bad_request)?;
+    create_ranges(
         &app_data.database,
         path.id,
         query.start_date,
         query.end_date,
+        range_bounds,
+        query.tails(),
+        query
+            .min_value
+            .map(round_value)
+            .unwrap_or(DEFAULT_MIN_VALUE),
     )
-    .await
-    {
-        Ok(_) => {
-            // if we're successful we want to get the
-            Ok(HttpResponse::TemporaryRedirect()
-                .append_header(("location", format!("/forecast/{}", path.id)))
-                .finish())
-        }
-        Err(e) => Ok(HttpResponse::NotFound().body(e.to_string())),
+    .await?;
+    Ok(HttpResponse::TemporaryRedirect()
+        .append_header(("location", format!("/forecast/{}", path.id)))
+        .finish())
+}
+
+#[derive(Deserialize)]
+pub struct GenerateNumericRangeQuery {
+    min: f64,
+    max: f64,
+    number_of_ranges: usize,
+    units: Option<String>,
+    // A checkbox, so only present when ticked.
+    log_scale: Option<String>,
+}
+
+pub async fn generate_numeric_ranges(
+    path: web::Path<EditPath>,
+    query: web::Query<GenerateNumericRangeQuery>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    if !query.min.is_finite() || !query.max.is_finite() {
+        return Err(AppError::bad_request(format!(
+            "The minimum and maximum must be numbers, but they were {} and {}",
+            query.min, query.max
+        )));
     }
+    create_numeric_ranges(
+        &app_data.database,
+        path.id,
+        query.min,
+        query.max,
+        query.number_of_ranges,
+        query.log_scale.is_some(),
+        query.units.unwrap_or_default().trim().to_string(),
+    )
+    .await?;
+    Ok(HttpResponse::TemporaryRedirect()
+        .append_header(("location", format!("/forecast/{}", path.id)))
+        .finish())
+}
+
+#[derive(Deserialize)]
+pub struct GenerateOutcomesQuery {
+    // One outcome label per line.
+    outcomes: String,
+}
+
+pub async fn generate_outcomes(
+    path: web::Path<EditPath>,
+    query: web::Query<GenerateOutcomesQuery>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let labels = query
+        .outcomes
+        .lines()
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string)
+        .collect();
+    create_outcomes(&app_data.database, path.id, labels).await?;
+    Ok(HttpResponse::TemporaryRedirect()
+        .append_header(("location", format!("/forecast/{}", path.id)))
+        .finish())
 }
@@ -0,0 +1,320 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::db::{ForecastData, RangeForecast, Revision, Scores};
+
+use super::ui::range::Range;
+
+// Scores a forecast's range values, given the index of the range the outcome
+// fell in. Values are turned into probabilities by dividing by their total.
+//
+// - The log score is the natural log of the probability given to the outcome's
+//   range. Higher is better and 0 is perfect. If the outcome's range was given
+//   nothing it would be -inf, so there is no log score.
+// - The Brier score sums the squared errors over every range. Lower is better,
+//   from 0 to 2.
+// - The ranked probability score sums the squared errors of the cumulative
+//   probabilities, so it rewards putting weight on ranges close to the
+//   outcome. It is divided by one less than the number of ranges to keep it
+//   between 0 and 1. Lower is better.
+pub fn score(range_values: &[f64], outcome_index: usize) -> Scores {
+    let total: f64 = range_values.iter().sum();
+    let probabilities: Vec<f64> = range_values
+        .iter()
+        .map(|value| if total > 0.0 { value / total } else { 0.0 })
+        .collect();
+    let outcome = |index: usize| if index == outcome_index { 1.0 } else { 0.0 };
+
+    let log = probabilities
+        .get(outcome_index)
+        .copied()
+        .filter(|probability| *probability > 0.0)
+        .map(f64::ln);
+    let brier = probabilities
+        .iter()
+        .enumerate()
+        .map(|(index, probability)| (probability - outcome(index)).powi(2))
+        .sum();
+
+    let mut cumulative_probability = 0.0;
+    let mut cumulative_outcome = 0.0;
+    let mut ranked_probability = 0.0;
+    for (index, probability) in probabilities
+        .iter()
+        .enumerate()
+        .take(probabilities.len().saturating_sub(1))
+    {
+        cumulative_probability += probability;
+        cumulative_outcome += outcome(index);
+        ranked_probability += (cumulative_probability - cumulative_outcome).powi(2);
+    }
+    if probabilities.len() > 1 {
+        ranked_probability /= (probabilities.len() - 1) as f64;
+    }
+
+    Scores {
+        log,
+        brier,
+        ranked_probability,
+    }
+}
+
+// Scores date ranges against the date the event happened. An outcome outside
+// every range, e.g. before the start date when there's no range for that,
+// can't be scored.
+pub fn score_ranges(ranges: &[Range], outcome_date: Option<NaiveDate>) -> Option<Scores> {
+    let outcome_index = ranges
+        .iter()
+        .position(|range| range.contains_outcome(outcome_date))?;
+    let range_values: Vec<f64> = ranges.iter().map(|range| range.value).collect();
+    Some(score(&range_values, outcome_index))
+}
+
+// Averages the scores of every revision, each weighted by how long it was the
+// latest: from when it was saved until the next one was, or until the forecast
+// was resolved, whichever came first. Revisions that can't be scored, or that
+// were saved after it was resolved, are left out.
+pub fn time_weighted_score(
+    revisions: &[Revision],
+    resolved_at: NaiveDateTime,
+    outcome_date: Option<NaiveDate>,
+) -> Option<Scores> {
+    let mut revisions: Vec<&Revision> = revisions.iter().collect();
+    // Revisions saved at the same time are in the order they were saved.
+    revisions.sort_by_key(|revision| (revision.created_at, revision.id));
+    let ends = revisions
+        .iter()
+        .skip(1)
+        .map(|revision| revision.created_at.min(resolved_at))
+        .chain(std::iter::once(resolved_at));
+
+    let mut total_weight = 0.0;
+    let mut weighted = Scores {
+        log: Some(0.0),
+        brier: 0.0,
+        ranked_probability: 0.0,
+    };
+    for (revision, end) in revisions.iter().zip(ends) {
+        let ranges = match &revision.data {
+            ForecastData::Date(RangeForecast {
+                ranges: Some(ranges),
+                ..
+            }) => ranges,
+            _ => continue,
+        };
+        let scores = match score_ranges(ranges, outcome_date) {
+            Some(scores) => scores,
+            None => continue,
+        };
+        let weight = (end - revision.created_at).num_milliseconds().max(0) as f64;
+        if weight == 0.0 {
+            continue;
+        }
+        total_weight += weight;
+        // Any time spent giving the outcome nothing leaves no log score.
+        weighted.log = weighted
+            .log
+            .zip(scores.log)
+            .map(|(total, log)| total + log * weight);
+        weighted.brier += scores.brier * weight;
+        weighted.ranked_probability += scores.ranked_probability * weight;
+    }
+    if total_weight == 0.0 {
+        return None;
+    }
+    Some(Scores {
+        log: weighted.log.map(|log| log / total_weight),
+        brier: weighted.brier / total_weight,
+        ranked_probability: weighted.ranked_probability / total_weight,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RangeForecast;
+    use crate::forecasts::ui::range::{equal_bounds, get_ranges, TailRanges};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    fn assert_scores(scores: Scores, log: f64, brier: f64, ranked_probability: f64) {
+        assert_close(scores.log.unwrap(), log);
+        assert_close(scores.brier, brier);
+        assert_close(scores.ranked_probability, ranked_probability);
+    }
+
+    // A ten percent tail either side of three ranges in June.
+    fn ranges_with_tails() -> Vec<Range> {
+        let range_bounds = equal_bounds(
+            NaiveDate::from_ymd(2022, 6, 1),
+            NaiveDate::from_ymd(2022, 6, 30),
+            3,
+        );
+        let tails = TailRanges {
+            before_start: true,
+            after_end: true,
+        };
+        get_ranges(&range_bounds, &[10.0, 30.0, 30.0, 20.0, 10.0], tails, 0.0)
+    }
+
+    #[test]
+    fn scores_an_outcome_in_the_middle() {
+        let scores = score(&[0.1, 0.3, 0.3, 0.2, 0.1], 2);
+        assert_scores(scores, 0.3_f64.ln(), 0.64, 0.0675);
+    }
+
+    #[test]
+    fn values_are_turned_into_probabilities() {
+        let scores = score(&[10.0, 30.0, 30.0, 20.0, 10.0], 2);
+        assert_scores(scores, 0.3_f64.ln(), 0.64, 0.0675);
+    }
+
+    #[test]
+    fn scores_a_perfect_forecast() {
+        assert_scores(score(&[0.0, 100.0, 0.0], 1), 0.0, 0.0, 0.0);
+    }
+
+    #[test]
+    fn an_outcome_given_nothing_has_no_log_score() {
+        let scores = score(&[50.0, 50.0, 0.0], 2);
+        assert_eq!(scores.log, None);
+        assert_close(scores.brier, 1.5);
+    }
+
+    #[test]
+    fn scores_an_outcome_in_the_range() {
+        let scores = score_ranges(&ranges_with_tails(), Some(NaiveDate::from_ymd(2022, 6, 15)));
+        assert_scores(scores.unwrap(), 0.3_f64.ln(), 0.64, 0.0675);
+    }
+
+    #[test]
+    fn scores_an_outcome_before_the_start_date() {
+        let scores = score_ranges(&ranges_with_tails(), Some(NaiveDate::from_ymd(2022, 5, 1)));
+        assert_scores(scores.unwrap(), 0.1_f64.ln(), 1.04, 0.3175);
+    }
+
+    #[test]
+    fn scores_an_event_that_never_happened_in_the_last_tail() {
+        let scores = score_ranges(&ranges_with_tails(), None);
+        assert_scores(scores.unwrap(), 0.1_f64.ln(), 1.04, 0.3675);
+    }
+
+    #[test]
+    fn an_outcome_outside_every_range_cant_be_scored() {
+        let range_bounds = equal_bounds(
+            NaiveDate::from_ymd(2022, 6, 1),
+            NaiveDate::from_ymd(2022, 6, 30),
+            2,
+        );
+        let ranges = get_ranges(&range_bounds, &[50.0, 50.0], TailRanges::default(), 0.0);
+        assert_eq!(
+            score_ranges(&ranges, Some(NaiveDate::from_ymd(2022, 7, 1))),
+            None
+        );
+    }
+
+    fn at(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2022, 6, 1).and_hms(hour, 0, 0)
+    }
+
+    // A revision of a two range forecast for June, saved at the given hour.
+    fn revision(id: i64, hour: u32, values: &[f64]) -> Revision {
+        let range_bounds = equal_bounds(
+            NaiveDate::from_ymd(2022, 6, 1),
+            NaiveDate::from_ymd(2022, 6, 30),
+            values.len(),
+        );
+        Revision {
+            id,
+            forecast_id: 1,
+            created_at: at(hour),
+            note: None,
+            data: ForecastData::Date(RangeForecast {
+                start_date: Some("2022-06-01".to_string()),
+                end_date: Some("2022-06-30".to_string()),
+                number_of_ranges: Some(values.len()),
+                min_value: None,
+                ranges: Some(get_ranges(
+                    &range_bounds,
+                    values,
+                    TailRanges::default(),
+                    0.0,
+                )),
+                distribution: None,
+            }),
+        }
+    }
+
+    // The outcome falls in the first range.
+    fn outcome() -> Option<NaiveDate> {
+        Some(NaiveDate::from_ymd(2022, 6, 3))
+    }
+
+    #[test]
+    fn a_single_revision_scores_as_itself() {
+        let revisions = [revision(1, 0, &[80.0, 20.0])];
+        let scores = time_weighted_score(&revisions, at(10), outcome()).unwrap();
+        assert_scores(scores, 0.8_f64.ln(), 0.08, 0.04);
+    }
+
+    #[test]
+    fn revisions_are_weighted_by_how_long_they_were_the_latest() {
+        // Half at 0.8 for three hours, then 0.4 for one.
+        let revisions = [revision(2, 3, &[40.0, 60.0]), revision(1, 0, &[80.0, 20.0])];
+        let scores = time_weighted_score(&revisions, at(4), outcome()).unwrap();
+        assert_scores(
+            scores,
+            (3.0 * 0.8_f64.ln() + 0.4_f64.ln()) / 4.0,
+            (3.0 * 0.08 + 0.72) / 4.0,
+            (3.0 * 0.04 + 0.36) / 4.0,
+        );
+    }
+
+    #[test]
+    fn revisions_after_resolution_are_left_out() {
+        // The second revision only counts until the forecast was resolved, at
+        // four, not until the third was saved.
+        let revisions = [
+            revision(1, 0, &[80.0, 20.0]),
+            revision(2, 3, &[40.0, 60.0]),
+            revision(3, 6, &[10.0, 90.0]),
+        ];
+        let scores = time_weighted_score(&revisions, at(4), outcome()).unwrap();
+        assert_scores(
+            scores,
+            (3.0 * 0.8_f64.ln() + 0.4_f64.ln()) / 4.0,
+            (3.0 * 0.08 + 0.72) / 4.0,
+            (3.0 * 0.04 + 0.36) / 4.0,
+        );
+    }
+
+    #[test]
+    fn of_revisions_saved_at_the_same_time_the_later_one_counts() {
+        // Newest first, as the database returns them.
+        let revisions = [revision(2, 2, &[40.0, 60.0]), revision(1, 2, &[80.0, 20.0])];
+        let scores = time_weighted_score(&revisions, at(4), outcome()).unwrap();
+        assert_scores(scores, 0.4_f64.ln(), 0.72, 0.36);
+    }
+
+    #[test]
+    fn a_revision_that_gave_the_outcome_nothing_leaves_no_log_score() {
+        let revisions = [revision(2, 3, &[80.0, 20.0]), revision(1, 0, &[0.0, 100.0])];
+        let scores = time_weighted_score(&revisions, at(4), outcome()).unwrap();
+        assert_eq!(scores.log, None);
+        assert_close(scores.brier, (3.0 * 2.0 + 0.08) / 4.0);
+        assert_close(scores.ranked_probability, (3.0 * 1.0 + 0.04) / 4.0);
+    }
+
+    #[test]
+    fn revisions_with_no_time_before_resolution_cant_be_scored() {
+        let revisions = [revision(1, 4, &[80.0, 20.0])];
+        assert_eq!(time_weighted_score(&revisions, at(4), outcome()), None);
+        assert_eq!(time_weighted_score(&[], at(4), outcome()), None);
+    }
+}
@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::{
+    db::{BinaryForecast, ForecastData, ForecastType},
+    error::AppError,
+    AppData,
+};
+
+use super::forecast::EditPath;
+
+pub const DEFAULT_PROBABILITY: i32 = 50;
+
+#[derive(Template)]
+#[template(path = "forecasts/_binary.html")]
+pub struct BinaryTemplate<'a> {
+    pub(crate) forecast_id: &'a str,
+    pub(crate) by_date: &'a str,
+    pub(crate) probability: i32,
+    pub(crate) saved: bool,
+}
+
+#[derive(Deserialize)]
+pub struct BinaryFormData {
+    // Empty until the user picks a date.
+    by_date: String,
+    probability: i32,
+}
+
+// Saves the binary forecast whenever either of its inputs changes.
+pub async fn update_binary(
+    path: web::Path<EditPath>,
+    form: web::Form<BinaryFormData>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    match database.read_by_id(path.id).await? {
+        Some(forecast) if forecast.forecast_type == ForecastType::Binary => (),
+        Some(_) => return Err(AppError::bad_request("Not a binary forecast")),
+        None => return Err(AppError::no_forecast(path.id)),
+    }
+    if !(0..=100).contains(&form.probability) {
+        return Err(AppError::bad_request(
+            "Probability must be between 0 and 100",
+        ));
+    }
+    let by_date = form.by_date.trim();
+    if !by_date.is_empty() && NaiveDate::parse_from_str(by_date, "%Y-%m-%d").is_err() {
+        return Err(AppError::bad_request("Date must be YYYY-MM-DD"));
+    }
+
+    let binary_forecast = BinaryForecast {
+        by_date: Some(by_date.to_string()).filter(|by_date| !by_date.is_empty()),
+        probability: Some(form.probability),
+    };
+    database
+        .update_data(path.id, ForecastData::Binary(binary_forecast), None)
+        .await?;
+
+    let s = BinaryTemplate {
+        forecast_id: path.id.to_string().as_str(),
+        by_date,
+        probability: form.probability,
+        saved: true,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
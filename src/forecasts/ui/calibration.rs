@@ -0,0 +1,130 @@
+use actix_web::{web, HttpResponse};
+use askama::Template;
+
+use crate::{
+    db::{ForecastData, RangeForecast},
+    error::AppError,
+    AppData,
+};
+
+const NUMBER_OF_BINS: usize = 10;
+// The reliability diagram is drawn in a square this many pixels wide, inside
+// a margin for the axis labels.
+const PLOT_SIZE: f64 = 300.0;
+const PLOT_MARGIN: f64 = 40.0;
+
+// Every range probability that falls between `lower` and `upper` percent,
+// pooled across all resolved forecasts. The last bin includes 100%.
+#[derive(Debug, Default, Clone)]
+pub struct CalibrationBin {
+    lower: usize,
+    upper: usize,
+    count: usize,
+    total_predicted: f64,
+    hits: usize,
+}
+
+impl CalibrationBin {
+    fn mean_predicted(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total_predicted / self.count as f64)
+    }
+
+    fn observed_frequency(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.hits as f64 / self.count as f64)
+    }
+
+    // Where the bin's point goes on the reliability diagram, if it has one.
+    fn point(&self) -> Option<(f64, f64)> {
+        Some((
+            plot_x(self.mean_predicted()?),
+            plot_y(self.observed_frequency()?),
+        ))
+    }
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/calibration.html")]
+pub struct CalibrationTemplate<'a> {
+    bins: &'a Vec<CalibrationBin>,
+    number_of_forecasts: usize,
+    plot_size: f64,
+    plot_margin: f64,
+    // The points of every non-empty bin, ready for an SVG polyline.
+    line: String,
+}
+
+impl<'a> CalibrationTemplate<'a> {
+    fn tick(&self, percent: &usize) -> f64 {
+        plot_x(*percent as f64 / 100.0)
+    }
+
+    fn tick_y(&self, percent: &usize) -> f64 {
+        plot_y(*percent as f64 / 100.0)
+    }
+}
+
+fn plot_x(probability: f64) -> f64 {
+    PLOT_MARGIN + probability * PLOT_SIZE
+}
+
+fn plot_y(probability: f64) -> f64 {
+    PLOT_MARGIN + (1.0 - probability) * PLOT_SIZE
+}
+
+// Compares how likely every resolved forecast said each of its ranges was with
+// how often the outcome actually fell in ranges given that probability.
+pub async fn calibration(app_data: web::Data<AppData>) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    let forecasts = database.find_resolved().await?;
+    let mut bins: Vec<CalibrationBin> = (0..NUMBER_OF_BINS)
+        .map(|index| CalibrationBin {
+            lower: index * 100 / NUMBER_OF_BINS,
+            upper: (index + 1) * 100 / NUMBER_OF_BINS,
+            ..Default::default()
+        })
+        .collect();
+    let mut number_of_forecasts = 0;
+    for forecast in forecasts {
+        let (resolution, ranges) = match (forecast.resolution, forecast.data) {
+            (
+                Some(resolution),
+                Some(ForecastData::Date(RangeForecast {
+                    ranges: Some(ranges),
+                    ..
+                })),
+            ) => (resolution, ranges),
+            _ => continue,
+        };
+        let total: f64 = ranges.iter().map(|range| range.value).sum();
+        if total <= 0.0 {
+            continue;
+        }
+        number_of_forecasts += 1;
+        for range in ranges {
+            let probability = range.value / total;
+            let index = ((probability * NUMBER_OF_BINS as f64) as usize).min(NUMBER_OF_BINS - 1);
+            let bin = &mut bins[index];
+            bin.count += 1;
+            bin.total_predicted += probability;
+            if range.contains_outcome(resolution.outcome_date) {
+                bin.hits += 1;
+            }
+        }
+    }
+    let line = bins
+        .iter()
+        .filter_map(|bin| bin.point())
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    let s = CalibrationTemplate {
+        bins: &bins,
+        number_of_forecasts,
+        plot_size: PLOT_SIZE,
+        plot_margin: PLOT_MARGIN,
+        line,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
@@ -0,0 +1,296 @@
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+
+use crate::{
+    db::{ForecastData, Revision},
+    error::AppError,
+    AppData,
+};
+
+use super::{
+    forecast::EditPath,
+    range::{round_value, Range, RangeKind},
+};
+
+// Points in time are passed around in this format, e.g. from the history page.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+// How one range, or outcome, changed between two revisions. A range that only
+// exists in one of them has no value in the other.
+pub struct RangeDiff {
+    pub(crate) label: String,
+    pub(crate) old_value: Option<f64>,
+    pub(crate) new_value: Option<f64>,
+}
+
+impl RangeDiff {
+    pub fn delta(&self) -> f64 {
+        round_value(self.new_value.unwrap_or(0.0) - self.old_value.unwrap_or(0.0))
+    }
+}
+
+// How much probability moved between two revisions, in percentage points.
+// `moved` is half the summed change over every stretch of days, and it's
+// split into what moved `earlier` and what moved `later`.
+pub struct Shift {
+    pub(crate) moved: f64,
+    pub(crate) earlier: f64,
+    pub(crate) later: f64,
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/diff.html")]
+pub struct DiffTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    from: &'a str,
+    to: &'a str,
+    old_revision: Option<&'a Revision>,
+    new_revision: Option<&'a Revision>,
+    diffs: &'a Vec<RangeDiff>,
+    shift: Option<Shift>,
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+// Compares a forecast as it was at two points in time.
+pub async fn diff(
+    path: web::Path<EditPath>,
+    query: web::Query<DiffQuery>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    let forecast = match database.read_by_id(path.id).await? {
+        Some(forecast) => forecast,
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    let from = query.from.as_deref().unwrap_or_default();
+    let to = query.to.as_deref().unwrap_or_default();
+    let (old_revision, new_revision) = match (parse_timestamp(from), parse_timestamp(to)) {
+        (Some(from), Some(to)) => (
+            database.read_revision_at(path.id, from).await?,
+            database.read_revision_at(path.id, to).await?,
+        ),
+        _ => (None, None),
+    };
+    let (diffs, shift) = match (&old_revision, &new_revision) {
+        (Some(old_revision), Some(new_revision)) => {
+            let diffs = diff_values(
+                &labelled_values(&old_revision.data),
+                &labelled_values(&new_revision.data),
+            );
+            let shift = match (&old_revision.data, &new_revision.data) {
+                (ForecastData::Date(old), ForecastData::Date(new)) => {
+                    match (&old.ranges, &new.ranges) {
+                        (Some(old), Some(new)) => Some(shift(old, new)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            (diffs, shift)
+        }
+        _ => (Vec::new(), None),
+    };
+    let body = DiffTemplate {
+        forecast_name: forecast.name.as_str(),
+        forecast_id: forecast.id.to_string().as_str(),
+        from,
+        to,
+        old_revision: old_revision.as_ref(),
+        new_revision: new_revision.as_ref(),
+        diffs: &diffs,
+        shift,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+}
+
+// Accepts our own timestamps, and the shorter ones a datetime-local input
+// sends.
+fn parse_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    [TIMESTAMP_FORMAT, "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(timestamp, format).ok())
+}
+
+// Every kind of forecast, reduced to its labelled values so they can be
+// compared.
+fn labelled_values(data: &ForecastData) -> Vec<(String, f64)> {
+    match data {
+        ForecastData::Date(data) => data
+            .ranges
+            .iter()
+            .flatten()
+            .map(|range| (range.label.clone(), range.value))
+            .collect(),
+        ForecastData::Numeric(data) => data
+            .ranges
+            .iter()
+            .flatten()
+            .map(|range| (range.label.clone(), range.value))
+            .collect(),
+        ForecastData::MultipleChoice(data) => data
+            .outcomes
+            .iter()
+            .flatten()
+            .map(|outcome| (outcome.label.clone(), outcome.value))
+            .collect(),
+        ForecastData::Binary(data) => {
+            let probability = data.probability.unwrap_or_default() as f64;
+            vec![
+                ("Yes".to_string(), probability),
+                ("No".to_string(), 100.0 - probability),
+            ]
+        }
+    }
+}
+
+// Ranges are matched up by label, so a range whose bounds changed shows up as
+// one that was removed and one that was added.
+fn diff_values(old: &[(String, f64)], new: &[(String, f64)]) -> Vec<RangeDiff> {
+    let find = |values: &[(String, f64)], label: &str| {
+        values
+            .iter()
+            .find(|(other_label, _)| other_label == label)
+            .map(|(_, value)| *value)
+    };
+    let mut diffs: Vec<RangeDiff> = new
+        .iter()
+        .map(|(label, value)| RangeDiff {
+            label: label.clone(),
+            old_value: find(old, label),
+            new_value: Some(*value),
+        })
+        .collect();
+    for (label, value) in old {
+        if find(new, label).is_none() {
+            diffs.push(RangeDiff {
+                label: label.clone(),
+                old_value: Some(*value),
+                new_value: None,
+            });
+        }
+    }
+    diffs
+}
+
+// The two revisions' ranges may not line up, so they're compared over the
+// stretches of days between every range end in either. Going from earliest to
+// latest, whatever a stretch gained is matched with what earlier stretches
+// lost, or the other way around, which tells us which way it moved.
+fn shift(old: &[Range], new: &[Range]) -> Shift {
+    let mut dates: Vec<NaiveDate> = old
+        .iter()
+        .chain(new.iter())
+        .filter(|range| !range.is_tail())
+        .map(|range| range.end)
+        .collect();
+    dates.sort();
+    dates.dedup();
+    let total = |ranges: &[Range]| ranges.iter().map(|range| range.value).sum::<f64>();
+    let mut change_by: Vec<f64> = vec![0.0];
+    change_by.extend(
+        dates
+            .iter()
+            .map(|date| probability_by(new, *date) - probability_by(old, *date)),
+    );
+    change_by.push(total(new) - total(old));
+    let mut earlier = 0.0;
+    let mut later = 0.0;
+    // Gains still to be matched with a later loss, or losses (negative) still
+    // to be matched with a later gain.
+    let mut unmatched = 0.0;
+    for pair in change_by.windows(2) {
+        let change = pair[1] - pair[0];
+        if unmatched > 0.0 && change < 0.0 {
+            earlier += f64::min(unmatched, -change);
+        } else if unmatched < 0.0 && change > 0.0 {
+            later += f64::min(-unmatched, change);
+        }
+        unmatched += change;
+    }
+    Shift {
+        moved: earlier + later,
+        earlier,
+        later,
+    }
+}
+
+// The chance, in percent, of it having happened by the end of a date,
+// assuming each range's probability is spread evenly over its days.
+pub fn probability_by(ranges: &[Range], date: NaiveDate) -> f64 {
+    ranges
+        .iter()
+        .map(|range| {
+            let value = range.value;
+            match range.kind {
+                RangeKind::BeforeStart if date >= range.end => value,
+                RangeKind::Within if date >= range.end => value,
+                RangeKind::Within if date >= range.start => {
+                    let days = (range.end - range.start).num_days() + 1;
+                    let days_by = (date - range.start).num_days() + 1;
+                    value * days_by as f64 / days as f64
+                }
+                _ => 0.0,
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecasts::ui::range::{equal_bounds, get_ranges, TailRanges};
+
+    fn ranges(values: &[f64]) -> Vec<Range> {
+        let range_bounds = equal_bounds(
+            NaiveDate::from_ymd(2022, 6, 1),
+            NaiveDate::from_ymd(2022, 6, 30),
+            values.len(),
+        );
+        get_ranges(&range_bounds, values, TailRanges::default(), 0.0)
+    }
+
+    fn assert_shift(shift: Shift, moved: f64, earlier: f64, later: f64) {
+        assert!((shift.moved - moved).abs() < 1e-9, "moved {}", shift.moved);
+        assert!(
+            (shift.earlier - earlier).abs() < 1e-9,
+            "earlier {}",
+            shift.earlier
+        );
+        assert!((shift.later - later).abs() < 1e-9, "later {}", shift.later);
+    }
+
+    #[test]
+    fn shift_is_half_the_summed_change() {
+        let shift = shift(&ranges(&[20.0, 60.0, 20.0]), &ranges(&[40.0, 20.0, 40.0]));
+        assert_shift(shift, 40.0, 20.0, 20.0);
+    }
+
+    #[test]
+    fn shift_counts_mass_moved_past_several_ranges_once() {
+        let shift = shift(&ranges(&[70.0, 10.0, 20.0]), &ranges(&[10.0, 10.0, 80.0]));
+        assert_shift(shift, 60.0, 0.0, 60.0);
+    }
+
+    #[test]
+    fn shift_compares_ranges_that_dont_line_up() {
+        // Days 11-15 lose their share to days 1-10, and days 16-20 theirs to
+        // days 21-30.
+        let shift = shift(&ranges(&[50.0, 50.0]), &ranges(&[50.0, 0.0, 50.0]));
+        assert_shift(shift, 100.0 / 3.0, 50.0 / 3.0, 50.0 / 3.0);
+    }
+
+    #[test]
+    fn unchanged_ranges_have_no_shift() {
+        let values = ranges(&[25.0, 25.0, 50.0]);
+        assert_shift(shift(&values, &values), 0.0, 0.0, 0.0);
+    }
+}
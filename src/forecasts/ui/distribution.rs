@@ -0,0 +1,116 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::{
+    db::{ForecastData, RangeForecast},
+    error::AppError,
+    forecasts::distribution::Distribution,
+    AppData,
+};
+
+use super::{
+    forecast::EditPath,
+    range::{get_ranges, parse_date, TailRanges},
+};
+
+// One form covers every shape. The middle date is the median, or for a
+// triangle the most likely date.
+#[derive(Deserialize)]
+pub struct DistributionFormData {
+    shape: String,
+    // Not used by the lognormal shape, so it may be empty.
+    #[serde(default)]
+    low: String,
+    middle: String,
+    high: String,
+}
+
+impl DistributionFormData {
+    fn distribution(&self) -> Result<Distribution, AppError> {
+        let middle = parse_date(self.middle.trim())?;
+        let high = parse_date(self.high.trim())?;
+        match self.shape.as_str() {
+            "Normal" => Ok(Distribution::Normal {
+                low: parse_date(self.low.trim())?,
+                median: middle,
+                high,
+            }),
+            "Lognormal" => Ok(Distribution::Lognormal {
+                median: middle,
+                high,
+            }),
+            "Triangular" => Ok(Distribution::Triangular {
+                low: parse_date(self.low.trim())?,
+                mode: middle,
+                high,
+            }),
+            shape => Err(AppError::bad_request(format!(
+                "There's no distribution shaped '{}'",
+                shape
+            ))),
+        }
+    }
+}
+
+// Works out a date forecast's range values from a distribution and saves them,
+// along with the distribution, as a new revision. The ranges themselves stay
+// as they are.
+pub async fn fit_distribution(
+    path: web::Path<EditPath>,
+    form: web::Form<DistributionFormData>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    let forecast = database
+        .read_by_id(path.id)
+        .await?
+        .ok_or_else(|| AppError::no_forecast(path.id))?;
+    let range_forecast = match forecast.data {
+        Some(ForecastData::Date(range_forecast)) => range_forecast,
+        _ => {
+            return Err(AppError::bad_request(
+                "Only date forecasts have a distribution",
+            ))
+        }
+    };
+    let (start_date, ranges) = match (&range_forecast.start_date, &range_forecast.ranges) {
+        (Some(start_date), Some(ranges)) => (parse_date(start_date)?, ranges),
+        _ => {
+            return Err(AppError::bad_request(
+                "Create the date ranges before fitting a distribution to them",
+            ))
+        }
+    };
+    let distribution = form.distribution()?;
+    let min_value = range_forecast.min_value.unwrap_or(0.0);
+    let range_values = distribution
+        .range_values(start_date, ranges, min_value)
+        .map_err(AppError::bad_request)?;
+    let range_bounds: Vec<_> = ranges
+        .iter()
+        .filter(|range| !range.is_tail())
+        .map(|range| (range.start, range.end))
+        .collect();
+    let ranges = get_ranges(
+        &range_bounds,
+        &range_values,
+        TailRanges::from_ranges(ranges),
+        min_value,
+    );
+    let note = format!(
+        "Fitted a {} distribution",
+        distribution.shape().to_lowercase()
+    );
+    let range_forecast = RangeForecast {
+        min_value: Some(min_value),
+        ranges: Some(ranges),
+        distribution: Some(distribution),
+        ..range_forecast
+    };
+    database
+        .update_data(path.id, ForecastData::Date(range_forecast), Some(note))
+        .await?;
+    Ok(HttpResponse::SeeOther()
+        .append_header(("location", format!("/forecast/{}", path.id)))
+        .finish())
+}
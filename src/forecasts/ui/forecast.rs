@@ -1,21 +1,36 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use askama::Template;
-use log::info;
+use log::{debug, info};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::str::FromStr;
 
 use crate::{
-    db::{ForecastType, NewForecast},
+    db::{
+        ForecastData, ForecastMetadata, ForecastType, NewForecast, Resolution, Revision,
+        SavedForecast,
+    },
+    error::AppError,
+    forecasts::{
+        api::{prefers_json, ForecastResponse},
+        distribution::Distribution,
+        percentile::Percentiles,
+    },
     AppData,
 };
 
-use super::range::Range;
+use super::{
+    binary::DEFAULT_PROBABILITY,
+    numeric::NumericRange,
+    outcome::Outcome,
+    range::{outcome_index, total_value, Range, TailRanges},
+};
 
 #[derive(Template)]
 #[template(path = "forecasts/forecast.html")]
 pub struct ForecastTemplate<'a> {
     forecast_name: &'a str,
     forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
 }
 
 #[derive(Template)]
@@ -23,48 +38,182 @@ pub struct ForecastTemplate<'a> {
 pub struct SavedForecastTemplate<'a> {
     forecast_name: &'a str,
     forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
     start_date: &'a str,
     end_date: &'a str,
+    number_of_ranges: usize,
+    tails: TailRanges,
     ranges: &'a Vec<Range>,
-    total: &'a i32,
+    total: &'a f64,
+    min_value: f64,
+    error: Option<String>,
+    saved: bool,
+    revision: Option<&'a Revision>,
+    resolution: Option<&'a Resolution>,
+    outcome_index: Option<usize>,
+    locked: bool,
+    distribution: Option<&'a Distribution>,
+    percentiles: Option<Percentiles>,
+}
+
+impl<'a> SavedForecastTemplate<'a> {
+    // The shape to offer first when fitting a distribution.
+    fn distribution_shape(&self) -> &str {
+        self.distribution
+            .map(Distribution::shape)
+            .unwrap_or("Normal")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/numeric_forecast.html")]
+pub struct NumericForecastTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/saved_numeric_forecast.html")]
+pub struct SavedNumericForecastTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+    min: f64,
+    max: f64,
+    log_scale: bool,
+    units: &'a str,
+    number_of_ranges: usize,
+    ranges: &'a Vec<NumericRange>,
+    total: &'a f64,
+    error: Option<String>,
+    saved: bool,
+    revision: Option<&'a Revision>,
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/binary_forecast.html")]
+pub struct BinaryForecastTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+    by_date: &'a str,
+    probability: i32,
+    saved: bool,
+    revision: Option<&'a Revision>,
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/multiple_choice_forecast.html")]
+pub struct MultipleChoiceForecastTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/saved_multiple_choice_forecast.html")]
+pub struct SavedMultipleChoiceForecastTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+    outcomes: &'a Vec<Outcome>,
+    total: &'a f64,
+    error: Option<String>,
+    saved: bool,
+    revision: Option<&'a Revision>,
 }
 
 #[derive(Template)]
 #[template(path = "forecasts/create.html")]
-pub struct CreateForecastTemplate;
+pub struct CreateForecastTemplate<'a> {
+    name: &'a str,
+    forecast_type: &'a str,
+    error: Option<String>,
+}
+
+pub const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Deserialize)]
+pub struct CreateFormData {
+    name: String,
+    forecast_type: Option<String>,
+}
+
+// Names must be unique too, but only the database can be sure of that.
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("A forecast needs a name"));
+    }
+    let length = name.chars().count();
+    if length > MAX_NAME_LENGTH {
+        return Err(anyhow::anyhow!(
+            "A forecast's name can be at most {} characters, but this one has {}",
+            MAX_NAME_LENGTH,
+            length
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create() -> Result<HttpResponse, AppError> {
+    info!("Inviting user to create a forecast.");
+    let body = CreateForecastTemplate {
+        name: "",
+        forecast_type: "Date",
+        error: None,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+}
 
-pub async fn create(
-    query: web::Query<HashMap<String, String>>,
+// Creates the forecast and sends the user to it. If it can't be created the
+// form is shown again, saying why.
+pub async fn create_forecast(
+    form: web::Form<CreateFormData>,
     app_data: web::Data<AppData>,
-) -> Result<HttpResponse> {
-    if let Some(forecast_name) = query.get("name") {
-        info!("Creating forecast '{}'", forecast_name);
-        let database = &app_data.database;
-        //TODO: don't do this. Rather let the user know it already exists.
-        let forecast = match database.read_by_name(forecast_name.to_string()).await {
-            Some(forecast) => {
-                info!("Found forecast '{}", forecast.id);
-                forecast
-            }
-            None => {
-                info!("Forecast does not exist -- creating {}", forecast_name);
-                let new_forecast = NewForecast {
-                    name: forecast_name.to_string(),
-                    forecast_type: ForecastType::Date, // TODO: pass this in
-                };
-                app_data.database.create(new_forecast).await.unwrap()
+) -> Result<HttpResponse, AppError> {
+    let name = form.name.trim();
+    let forecast_type = match form.forecast_type.as_deref() {
+        Some(forecast_type) => ForecastType::from_str(forecast_type).map_err(|_| {
+            AppError::bad_request(format!("Invalid forecast type '{}'", forecast_type))
+        })?,
+        None => ForecastType::Date,
+    };
+    info!("Creating forecast '{}'", name);
+    let database = &app_data.database;
+    let result = match validate_name(name) {
+        Ok(()) => {
+            let new_forecast = NewForecast {
+                name: name.to_string(),
+                forecast_type,
+            };
+            database.create(new_forecast).await.map_err(AppError::from)
+        }
+        Err(e) => Err(AppError::bad_request(e)),
+    };
+    match result {
+        Ok(forecast) => {
+            // Now that we've created the forecast we're going to redirect the
+            // user using the new ID.
+            let redirect_url = format!("/forecast/{}", forecast.id);
+            Ok(HttpResponse::SeeOther()
+                .insert_header(("LOCATION", redirect_url))
+                .finish())
+        }
+        Err(AppError::Internal(e)) => Err(AppError::Internal(e)),
+        Err(e) => {
+            let forecast_type = form.forecast_type.as_deref().unwrap_or("Date");
+            let body = CreateForecastTemplate {
+                name,
+                forecast_type,
+                error: Some(e.to_string()),
             }
-        };
-        // Now that we've created the forecast we're going to redirect the user
-        // using the new ID.
-        let redirect_url = format!("/forecast/{}", forecast.id);
-        Ok(HttpResponse::SeeOther()
-            .insert_header(("LOCATION", redirect_url))
-            .finish())
-    } else {
-        info!("Inviting user to create a forecast.");
-        let body = CreateForecastTemplate.render().unwrap();
-        Ok(HttpResponse::Ok().content_type("text/html").body(body))
+            .render()?;
+            Ok(HttpResponse::build(e.status_code())
+                .content_type("text/html")
+                .body(body))
+        }
     }
 }
 
@@ -73,60 +222,172 @@ pub struct EditPath {
     pub id: i64,
 }
 
+// Answers with JSON instead of the page if the client asks for it.
 pub async fn edit(
     path: web::Path<EditPath>,
+    request: HttpRequest,
     // query: web::Query<HashMap<String, String>>,
     app_data: web::Data<AppData>,
-) -> Result<HttpResponse> {
-    println!("Editing forecast with id {}", path.id);
+) -> Result<HttpResponse, AppError> {
+    debug!("Editing forecast with id {}", path.id);
     let database = &app_data.database;
-    let maybe_forecast = database.read_by_id(path.id).await;
-    print!("{:#?}", maybe_forecast);
+    let maybe_forecast = database.read_by_id(path.id).await?;
     match maybe_forecast {
+        Some(forecast) if prefers_json(&request) => {
+            Ok(HttpResponse::Ok().json(ForecastResponse::from(forecast)))
+        }
         Some(forecast) => {
-            if forecast.data.is_none() {
-                println!("Found forecast {}", forecast.id);
-                let body = ForecastTemplate {
-                    forecast_name: forecast.name.as_str(),
-                    forecast_id: forecast.id.to_string().as_str(),
+            let body = render_forecast(&forecast, None)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(body))
+        }
+        None => Err(AppError::no_forecast(path.id)),
+    }
+}
+
+// Saved data should always have its fields filled in, so a gap means the
+// database is in a state we don't understand.
+fn missing(forecast: &SavedForecast, field: &str) -> AppError {
+    AppError::Internal(anyhow::anyhow!(
+        "Forecast {} is missing its {}",
+        forecast.id,
+        field
+    ))
+}
+
+// Renders the page for a forecast. Given a revision, the page shows the
+// forecast's data as it was then instead of as it is now.
+pub fn render_forecast(
+    forecast: &SavedForecast,
+    revision: Option<&Revision>,
+) -> Result<String, AppError> {
+    let data = match revision {
+        Some(revision) => Some(&revision.data),
+        None => forecast.data.as_ref(),
+    };
+    let body = match data {
+        Some(ForecastData::Date(data)) => {
+            debug!("Found saved forecast {}", forecast.id);
+            let ranges = data
+                .ranges
+                .as_ref()
+                .ok_or_else(|| missing(forecast, "ranges"))?;
+            SavedForecastTemplate {
+                forecast_name: forecast.name.as_str(),
+                forecast_id: forecast.id.to_string().as_str(),
+                metadata: &forecast.metadata,
+                start_date: data
+                    .start_date
+                    .as_deref()
+                    .ok_or_else(|| missing(forecast, "start date"))?,
+                end_date: data
+                    .end_date
+                    .as_deref()
+                    .ok_or_else(|| missing(forecast, "end date"))?,
+                number_of_ranges: data.number_of_ranges.unwrap_or(ranges.len()),
+                tails: TailRanges::from_ranges(ranges),
+                ranges,
+                total: &total_value(ranges.iter().map(|x| x.value)),
+                min_value: data.min_value.unwrap_or(0.0),
+                error: None,
+                saved: false,
+                revision,
+                resolution: forecast.resolution.as_ref(),
+                outcome_index: outcome_index(ranges, forecast.resolution.as_ref()),
+                locked: forecast.resolution.is_some(),
+                distribution: data.distribution.as_ref(),
+                percentiles: Percentiles::from_ranges(ranges),
+            }
+            .render()
+        }
+        Some(ForecastData::Numeric(data)) => {
+            debug!("Found saved numeric forecast {}", forecast.id);
+            let ranges = data
+                .ranges
+                .as_ref()
+                .ok_or_else(|| missing(forecast, "ranges"))?;
+            SavedNumericForecastTemplate {
+                forecast_name: forecast.name.as_str(),
+                forecast_id: forecast.id.to_string().as_str(),
+                metadata: &forecast.metadata,
+                min: data.min.ok_or_else(|| missing(forecast, "minimum"))?,
+                max: data.max.ok_or_else(|| missing(forecast, "maximum"))?,
+                log_scale: data.log_scale.unwrap_or(false),
+                units: data.units.as_deref().unwrap_or_default(),
+                number_of_ranges: data.number_of_ranges.unwrap_or(ranges.len()),
+                ranges,
+                total: &total_value(ranges.iter().map(|x| x.value)),
+                error: None,
+                saved: false,
+                revision,
+            }
+            .render()
+        }
+        Some(ForecastData::Binary(data)) => {
+            debug!("Found saved binary forecast {}", forecast.id);
+            BinaryForecastTemplate {
+                forecast_name: forecast.name.as_str(),
+                forecast_id: forecast.id.to_string().as_str(),
+                metadata: &forecast.metadata,
+                by_date: data.by_date.as_deref().unwrap_or_default(),
+                probability: data.probability.unwrap_or(DEFAULT_PROBABILITY),
+                saved: false,
+                revision,
+            }
+            .render()
+        }
+        Some(ForecastData::MultipleChoice(data)) => {
+            debug!("Found saved multiple choice forecast {}", forecast.id);
+            let outcomes = data
+                .outcomes
+                .as_ref()
+                .ok_or_else(|| missing(forecast, "outcomes"))?;
+            SavedMultipleChoiceForecastTemplate {
+                forecast_name: forecast.name.as_str(),
+                forecast_id: forecast.id.to_string().as_str(),
+                metadata: &forecast.metadata,
+                outcomes,
+                total: &total_value(outcomes.iter().map(|x| x.value)),
+                error: None,
+                saved: false,
+                revision,
+            }
+            .render()
+        }
+        None => {
+            debug!("Found forecast {}", forecast.id);
+            let forecast_name = forecast.name.as_str();
+            let forecast_id = forecast.id.to_string();
+            match forecast.forecast_type {
+                ForecastType::Date => ForecastTemplate {
+                    forecast_name,
+                    forecast_id: forecast_id.as_str(),
+                    metadata: &forecast.metadata,
+                }
+                .render(),
+                ForecastType::Numeric => NumericForecastTemplate {
+                    forecast_name,
+                    forecast_id: forecast_id.as_str(),
+                    metadata: &forecast.metadata,
                 }
-                .render()
-                .unwrap();
-                Ok(HttpResponse::Ok().content_type("text/html").body(body))
-            } else {
-                println!("Found saved forecast {}", forecast.id);
-                let ranges = forecast.data.as_ref().unwrap().ranges.as_ref().unwrap();
-                let body = SavedForecastTemplate {
-                    forecast_name: forecast.name.as_str(),
-                    forecast_id: forecast.id.to_string().as_str(),
-                    start_date: forecast
-                        .data
-                        .as_ref()
-                        .unwrap()
-                        .start_date
-                        .as_ref()
-                        .unwrap()
-                        .to_string()
-                        .as_str(),
-                    end_date: forecast
-                        .data
-                        .as_ref()
-                        .unwrap()
-                        .end_date
-                        .as_ref()
-                        .unwrap()
-                        .to_string()
-                        .as_str(),
-                    ranges,
-                    total: &ranges.iter().map(|x| x.value).sum(),
+                .render(),
+                ForecastType::Binary => BinaryForecastTemplate {
+                    forecast_name,
+                    forecast_id: forecast_id.as_str(),
+                    metadata: &forecast.metadata,
+                    by_date: "",
+                    probability: DEFAULT_PROBABILITY,
+                    saved: false,
+                    revision: None,
                 }
-                .render()
-                .unwrap();
-                Ok(HttpResponse::Ok().content_type("text/html").body(body))
+                .render(),
+                ForecastType::MultipleChoice => MultipleChoiceForecastTemplate {
+                    forecast_name,
+                    forecast_id: forecast_id.as_str(),
+                    metadata: &forecast.metadata,
+                }
+                .render(),
             }
-            // TODO: if there's no date then ask for a date
-            // TODO: if there's a date then show ranges
         }
-        None => Ok(HttpResponse::NotFound().finish()),
-    }
+    }?;
+    Ok(body)
 }
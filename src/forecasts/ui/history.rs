@@ -0,0 +1,71 @@
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use serde::Deserialize;
+
+use crate::{db::Revision, error::AppError, AppData};
+
+use super::forecast::{render_forecast, EditPath};
+
+#[derive(Template)]
+#[template(path = "forecasts/history.html")]
+pub struct HistoryTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    revisions: &'a Vec<Revision>,
+}
+
+impl<'a> HistoryTemplate<'a> {
+    // Revisions run newest first, so the one saved before the revision at a
+    // 1-based loop index comes straight after it.
+    fn previous(&self, loop_index: &usize) -> Option<&Revision> {
+        self.revisions.get(*loop_index)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RevisionPath {
+    pub id: i64,
+    pub revision_id: i64,
+}
+
+// Lists every revision of a forecast, newest first.
+pub async fn history(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    let forecast = match database.read_by_id(path.id).await? {
+        Some(forecast) => forecast,
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    let revisions = database.find_revisions(path.id).await?;
+    let body = HistoryTemplate {
+        forecast_name: forecast.name.as_str(),
+        forecast_id: forecast.id.to_string().as_str(),
+        revisions: &revisions,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(body))
+}
+
+// Shows a forecast as it was at a past revision.
+pub async fn revision(
+    path: web::Path<RevisionPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    let forecast = match database.read_by_id(path.id).await? {
+        Some(forecast) => forecast,
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    match database.read_revision(path.id, path.revision_id).await? {
+        Some(revision) => {
+            let body = render_forecast(&forecast, Some(&revision))?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(body))
+        }
+        None => Err(AppError::not_found(format!(
+            "Forecast {} has no revision {}",
+            path.id, path.revision_id
+        ))),
+    }
+}
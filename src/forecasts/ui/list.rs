@@ -1,13 +1,9 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse};
 use askama::Template;
 
-use crate::{db::SavedForecast, AppData};
+use crate::{db::SavedForecast, error::AppError, forecasts::api::prefers_json, AppData};
 
-// #[derive(Template)]
-// #[template(path = "forecasts/_list.html")]
-// struct ListTemplate<'a> {
-//     forecasts: &'a Vec<String>,
-// }
+use super::forecast::EditPath;
 
 #[derive(Template)]
 #[template(path = "forecasts/list.html")]
@@ -15,12 +11,27 @@ struct ListTemplate<'a> {
     forecasts: &'a Vec<SavedForecast>,
 }
 
-pub async fn list(app_data: web::Data<AppData>) -> Result<HttpResponse> {
-    let results = app_data.database.find().await.unwrap();
+// Answers with JSON instead of the page if the client asks for it.
+pub async fn list(
+    request: HttpRequest,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let results = app_data.database.find().await?;
+    if prefers_json(&request) {
+        return Ok(HttpResponse::Ok().json(results));
+    }
     let s = ListTemplate {
         forecasts: &results,
     }
-    .render()
-    .unwrap();
+    .render()?;
     Ok(HttpResponse::Ok().content_type("text/html").body(s))
 }
+
+// Deletes a forecast from the list. The empty response replaces its list item.
+pub async fn delete(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    app_data.database.delete(path.id).await?;
+    Ok(HttpResponse::Ok().content_type("text/html").finish())
+}
@@ -0,0 +1,138 @@
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use serde::Deserialize;
+
+use crate::{db::ForecastMetadata, error::AppError, AppData};
+
+use super::forecast::{validate_name, EditPath};
+
+#[derive(Template)]
+#[template(path = "forecasts/_metadata.html")]
+pub struct MetadataTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/_edit_metadata.html")]
+pub struct EditMetadataTemplate<'a> {
+    forecast_name: &'a str,
+    forecast_id: &'a str,
+    metadata: &'a ForecastMetadata,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MetadataFormData {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    criteria: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    owner: String,
+}
+
+impl MetadataFormData {
+    // Blank fields are left unset, and tags are split on commas.
+    fn metadata(&self) -> ForecastMetadata {
+        let optional =
+            |value: &str| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        ForecastMetadata {
+            description: optional(&self.description),
+            criteria: optional(&self.criteria),
+            tags: self
+                .tags
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            owner: optional(&self.owner),
+        }
+    }
+}
+
+// Shows a forecast's name and metadata, e.g. after cancelling an edit.
+pub async fn metadata(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let forecast = match app_data.database.read_by_id(path.id).await? {
+        Some(forecast) => forecast,
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    let s = MetadataTemplate {
+        forecast_name: forecast.name.as_str(),
+        forecast_id: forecast.id.to_string().as_str(),
+        metadata: &forecast.metadata,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
+
+// Replaces a forecast's name and metadata with a form to edit them.
+pub async fn edit_metadata(
+    path: web::Path<EditPath>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let forecast = match app_data.database.read_by_id(path.id).await? {
+        Some(forecast) => forecast,
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    let s = EditMetadataTemplate {
+        forecast_name: forecast.name.as_str(),
+        forecast_id: forecast.id.to_string().as_str(),
+        metadata: &forecast.metadata,
+        error: None,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
+
+// Saves a forecast's name and metadata. Invalid changes are shown in the form
+// again along with what's wrong with them.
+pub async fn update(
+    path: web::Path<EditPath>,
+    form: web::Form<MetadataFormData>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    let mut forecast = match database.read_by_id(path.id).await? {
+        Some(forecast) => forecast,
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    let name = form.name.trim();
+    let metadata = form.metadata();
+    let error = match validate_name(name) {
+        Ok(()) => {
+            forecast.name = name.to_string();
+            forecast.metadata = metadata.clone();
+            match database.update(forecast).await.map_err(AppError::from) {
+                Ok(()) => None,
+                Err(AppError::Internal(e)) => return Err(AppError::Internal(e)),
+                Err(e) => Some(e.to_string()),
+            }
+        }
+        Err(e) => Some(e.to_string()),
+    };
+    let forecast_id = path.id.to_string();
+    let s = match error {
+        Some(error) => EditMetadataTemplate {
+            forecast_name: name,
+            forecast_id: forecast_id.as_str(),
+            metadata: &metadata,
+            error: Some(error),
+        }
+        .render(),
+        None => MetadataTemplate {
+            forecast_name: name,
+            forecast_id: forecast_id.as_str(),
+            metadata: &metadata,
+        }
+        .render(),
+    }?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
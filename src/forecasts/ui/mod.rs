@@ -1,3 +1,12 @@
+pub mod binary;
+pub mod calibration;
+pub mod diff;
+pub mod distribution;
 pub mod forecast;
+pub mod history;
 pub mod list;
+pub mod metadata;
+pub mod numeric;
+pub mod outcome;
 pub mod range;
+pub mod resolution;
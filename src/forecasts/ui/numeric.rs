@@ -0,0 +1,402 @@
+use actix_web::HttpResponse;
+use askama::Template;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{Database, ForecastData, NumericForecast},
+    error::AppError,
+};
+
+use super::range::{
+    can_ceil, can_floor, required_field, save_data, total_value, uniform_values, validate_count,
+    validate_values, SaveTo, MAX_NUMBER_OF_RANGES, MIN_NUMBER_OF_RANGES,
+};
+
+// A numeric range covers everything from `min` up to, but not including,
+// `max`. The last range in a forecast also includes its `max`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct NumericRange {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) label: String,
+    pub(crate) value: f64,
+    pub(crate) can_ceil: bool,
+    pub(crate) can_floor: bool,
+}
+
+impl std::fmt::Display for NumericRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "min: {}", self.min)?;
+        write!(f, "max: {}", self.max)?;
+        write!(f, "label: {}", self.label)?;
+        write!(f, "value: {}", self.value)?;
+        Ok(())
+    }
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/_numeric.html")]
+pub struct NumericRangesTemplate<'a> {
+    pub(crate) forecast_id: &'a str,
+    pub(crate) ranges: &'a Vec<NumericRange>,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) log_scale: bool,
+    pub(crate) units: &'a str,
+    pub(crate) total: &'a f64,
+    pub(crate) error: Option<String>,
+    pub(crate) saved: bool,
+}
+
+// Everything about a numeric forecast's ranges bar their values, which are
+// read the same way for every forecast type.
+pub struct NumericFormData {
+    min: f64,
+    max: f64,
+    log_scale: bool,
+    units: String,
+    range_bounds: Option<Vec<(f64, f64)>>,
+}
+
+impl NumericFormData {
+    pub fn from_pairs(pairs: &[(String, String)]) -> Result<NumericFormData, AppError> {
+        let get = |name: &str| {
+            pairs
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| AppError::bad_request(format!("'{}' isn't a number", value)))
+        };
+        let numbers = |name: &str| -> Result<Vec<f64>, AppError> {
+            pairs
+                .iter()
+                .filter(|(key, _)| key == name)
+                .map(|(_, value)| number(value))
+                .collect()
+        };
+        let range_mins = numbers("range_min")?;
+        let range_maxes = numbers("range_max")?;
+        if range_mins.len() != range_maxes.len() {
+            return Err(AppError::bad_request(format!(
+                "There are {} range minimums but {} range maximums",
+                range_mins.len(),
+                range_maxes.len()
+            )));
+        }
+        let range_bounds = if range_mins.is_empty() {
+            None
+        } else {
+            Some(range_mins.into_iter().zip(range_maxes).collect())
+        };
+        Ok(NumericFormData {
+            min: number(required_field(pairs, "min")?)?,
+            max: number(required_field(pairs, "max")?)?,
+            log_scale: get("log_scale") == Some("true"),
+            units: get("units").unwrap_or_default().to_string(),
+            range_bounds,
+        })
+    }
+
+    fn range_bounds(&self, number_of_values: usize) -> Vec<(f64, f64)> {
+        match &self.range_bounds {
+            Some(range_bounds) => range_bounds.clone(),
+            None => numeric_bounds(self.min, self.max, number_of_values, self.log_scale),
+        }
+    }
+}
+
+pub async fn render_numeric_ranges(
+    form: &NumericFormData,
+    range_values: &[f64],
+    forecast_id: &str,
+    save_to: Option<&SaveTo<'_>>,
+    error: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let range_bounds = form.range_bounds(range_values.len());
+    let mut error = error.or_else(|| {
+        validate_numeric_bounds(form.min, form.max, &range_bounds)
+            .and_then(|()| validate_count(range_values, range_bounds.len()))
+            .and_then(|()| validate_values(range_values, 0.0))
+            .err()
+            .map(|e| e.to_string())
+    });
+    let ranges = get_numeric_ranges(&range_bounds, range_values, &form.units);
+    let total = total_value(range_values.iter().copied());
+    let mut saved = false;
+    if let (Some(save_to), None) = (save_to, &error) {
+        let numeric_forecast = NumericForecast {
+            min: Some(form.min),
+            max: Some(form.max),
+            log_scale: Some(form.log_scale),
+            units: Some(form.units.clone()),
+            number_of_ranges: Some(range_bounds.len()),
+            ranges: Some(ranges.clone()),
+        };
+        match save_data(save_to, total, ForecastData::Numeric(numeric_forecast)).await {
+            Ok(()) => saved = true,
+            Err(AppError::Internal(e)) => return Err(AppError::Internal(e)),
+            Err(e) => error = Some(e.to_string()),
+        }
+    }
+    let s = NumericRangesTemplate {
+        forecast_id,
+        ranges: &ranges,
+        min: form.min,
+        max: form.max,
+        log_scale: form.log_scale,
+        units: &form.units,
+        total: &total,
+        error,
+        saved,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
+
+// Creates the initial set of ranges for a numeric forecast.
+pub async fn create_numeric_ranges(
+    database: &Database,
+    id: i64,
+    min: f64,
+    max: f64,
+    number_of_ranges: usize,
+    log_scale: bool,
+    units: String,
+) -> Result<(), AppError> {
+    if !(MIN_NUMBER_OF_RANGES..=MAX_NUMBER_OF_RANGES).contains(&number_of_ranges) {
+        return Err(AppError::bad_request(format!(
+            "A forecast needs between {} and {} ranges, but {} were requested",
+            MIN_NUMBER_OF_RANGES, MAX_NUMBER_OF_RANGES, number_of_ranges
+        )));
+    }
+    if log_scale && min <= 0.0 {
+        return Err(AppError::bad_request(format!(
+            "A log scale needs a minimum above zero, but it was {}",
+            min
+        )));
+    }
+    let range_bounds = numeric_bounds(min, max, number_of_ranges, log_scale);
+    validate_numeric_bounds(min, max, &range_bounds).map_err(AppError::bad_request)?;
+    let range_values = uniform_values(number_of_ranges);
+    let numeric_forecast = NumericForecast {
+        min: Some(min),
+        max: Some(max),
+        log_scale: Some(log_scale),
+        number_of_ranges: Some(number_of_ranges),
+        ranges: Some(get_numeric_ranges(&range_bounds, &range_values, &units)),
+        units: Some(units),
+    };
+    database
+        .update_data(id, ForecastData::Numeric(numeric_forecast), None)
+        .await?;
+    Ok(())
+}
+
+// Splits the interval into ranges that are equally wide, or equally wide on a
+// log scale, in which case each range covers the same ratio.
+pub fn numeric_bounds(
+    min: f64,
+    max: f64,
+    number_of_ranges: usize,
+    log_scale: bool,
+) -> Vec<(f64, f64)> {
+    let edges: Vec<f64> = (0..=number_of_ranges)
+        .map(|index| {
+            let fraction = index as f64 / number_of_ranges as f64;
+            if index == 0 {
+                min
+            } else if index == number_of_ranges {
+                max
+            } else if log_scale {
+                min * (max / min).powf(fraction)
+            } else {
+                min + (max - min) * fraction
+            }
+        })
+        .collect();
+    edges.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// Ranges must run back to back, without gaps or overlaps, from the minimum to
+// the maximum.
+pub fn validate_numeric_bounds(
+    min: f64,
+    max: f64,
+    range_bounds: &[(f64, f64)],
+) -> anyhow::Result<()> {
+    if !min.is_finite() || !max.is_finite() {
+        return Err(anyhow::anyhow!(
+            "The minimum and maximum must be numbers, but they were {} and {}",
+            min,
+            max
+        ));
+    }
+    if min >= max {
+        return Err(anyhow::anyhow!(
+            "The minimum {} must be less than the maximum {}",
+            format_number(min),
+            format_number(max)
+        ));
+    }
+    let (first, last) = match (range_bounds.first(), range_bounds.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(anyhow::anyhow!("A forecast needs at least one range")),
+    };
+    if first.0 != min {
+        return Err(anyhow::anyhow!(
+            "The first range starts at {} but the forecast starts at {}",
+            format_number(first.0),
+            format_number(min)
+        ));
+    }
+    if last.1 != max {
+        return Err(anyhow::anyhow!(
+            "The last range ends at {} but the forecast ends at {}",
+            format_number(last.1),
+            format_number(max)
+        ));
+    }
+    for (range_min, range_max) in range_bounds {
+        if range_max <= range_min {
+            return Err(anyhow::anyhow!(
+                "The range starting at {} must end above it",
+                format_number(*range_min)
+            ));
+        }
+    }
+    for pair in range_bounds.windows(2) {
+        let (previous_max, next_min) = (pair[0].1, pair[1].0);
+        if next_min < previous_max {
+            return Err(anyhow::anyhow!(
+                "The range starting at {} overlaps the one before it",
+                format_number(next_min)
+            ));
+        }
+        if next_min > previous_max {
+            return Err(anyhow::anyhow!(
+                "There is a gap between {} and {}",
+                format_number(previous_max),
+                format_number(next_min)
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Keeps labels readable: whole numbers stay whole, anything else is shown to
+// about four significant figures.
+pub fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 {
+        return format!("{:.0}", number);
+    }
+    let magnitude = number.abs().log10().floor() as i32;
+    let decimals = (3 - magnitude).clamp(0, 10) as usize;
+    let formatted = format!("{:.*}", decimals, number);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn get_numeric_ranges(
+    range_bounds: &[(f64, f64)],
+    range_values: &[f64],
+    units: &str,
+) -> Vec<NumericRange> {
+    let total = total_value(range_values.iter().copied());
+    range_bounds
+        .iter()
+        .enumerate()
+        .map(|(index, (min, max))| {
+            let this_value = *range_values.get(index).unwrap_or(&0.0);
+            let label = format!(
+                "{} - {} {}",
+                format_number(*min),
+                format_number(*max),
+                units
+            )
+            .trim_end()
+            .to_string();
+            NumericRange {
+                min: *min,
+                max: *max,
+                label,
+                value: this_value,
+                can_floor: can_floor(total, this_value, 0.0),
+                can_ceil: can_ceil(total),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn infinite_or_nan_min_and_max_are_rejected() {
+        for (min, max) in [
+            (f64::NEG_INFINITY, f64::INFINITY),
+            (0.0, f64::INFINITY),
+            (f64::NAN, 10.0),
+            (0.0, f64::NAN),
+        ] {
+            let range_bounds = numeric_bounds(min, max, 3, false);
+            assert!(
+                validate_numeric_bounds(min, max, &range_bounds).is_err(),
+                "{} to {} was accepted",
+                min,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn form_data_rejects_non_finite_numbers() {
+        for (min, max) in [("-inf", "inf"), ("0", "NaN"), ("infinity", "10")] {
+            let form = NumericFormData::from_pairs(&pairs(&[("min", min), ("max", max)]));
+            assert!(form.is_err(), "{} to {} was accepted", min, max);
+        }
+        let form = NumericFormData::from_pairs(&pairs(&[
+            ("min", "0"),
+            ("max", "10"),
+            ("range_min", "0"),
+            ("range_max", "inf"),
+        ]));
+        assert!(form.is_err());
+    }
+
+    #[test]
+    fn form_data_rejects_unpaired_range_bounds() {
+        let form = NumericFormData::from_pairs(&pairs(&[
+            ("min", "0"),
+            ("max", "20"),
+            ("range_min", "0"),
+            ("range_max", "10"),
+            ("range_min", "10"),
+        ]));
+        assert!(form.is_err());
+    }
+
+    #[test]
+    fn finite_bounds_are_accepted() {
+        let range_bounds = numeric_bounds(0.0, 10.0, 3, false);
+        assert!(validate_numeric_bounds(0.0, 10.0, &range_bounds).is_ok());
+        let form = NumericFormData::from_pairs(&pairs(&[("min", "0"), ("max", "10")])).unwrap();
+        assert_eq!((form.min, form.max), (0.0, 10.0));
+    }
+}
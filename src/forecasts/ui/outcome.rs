@@ -0,0 +1,159 @@
+use actix_web::HttpResponse;
+use askama::Template;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{Database, ForecastData, MultipleChoiceForecast},
+    error::AppError,
+};
+
+use super::range::{
+    can_ceil, can_floor, save_data, total_value, uniform_values, validate_count, SaveTo,
+    MAX_NUMBER_OF_RANGES, MIN_NUMBER_OF_RANGES,
+};
+
+// One of the possible answers to a multiple choice forecast. These are
+// adjusted exactly like ranges, so they share their fields.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
+pub struct Outcome {
+    pub(crate) label: String,
+    pub(crate) value: f64,
+    pub(crate) can_ceil: bool,
+    pub(crate) can_floor: bool,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "label: {}", self.label)?;
+        write!(f, "value: {}", self.value)?;
+        Ok(())
+    }
+}
+
+#[derive(Template)]
+#[template(path = "forecasts/_outcome.html")]
+pub struct OutcomesTemplate<'a> {
+    pub(crate) forecast_id: &'a str,
+    pub(crate) outcomes: &'a Vec<Outcome>,
+    pub(crate) total: &'a f64,
+    pub(crate) error: Option<String>,
+    pub(crate) saved: bool,
+}
+
+// The outcome labels are submitted as repeated `outcome_label` fields, in the
+// same order as their values.
+pub struct OutcomeFormData {
+    labels: Vec<String>,
+}
+
+impl OutcomeFormData {
+    pub fn from_pairs(pairs: &[(String, String)]) -> Result<OutcomeFormData, AppError> {
+        Ok(OutcomeFormData {
+            labels: pairs
+                .iter()
+                .filter(|(key, _)| key == "outcome_label")
+                .map(|(_, value)| value.trim().to_string())
+                .collect(),
+        })
+    }
+}
+
+pub async fn render_outcomes(
+    form: &OutcomeFormData,
+    outcome_values: &[f64],
+    forecast_id: &str,
+    save_to: Option<&SaveTo<'_>>,
+    error: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let mut error = error.or_else(|| {
+        validate_labels(&form.labels)
+            .and_then(|()| validate_count(outcome_values, form.labels.len()))
+            .err()
+            .map(|e| e.to_string())
+    });
+    let outcomes = get_outcomes(&form.labels, outcome_values);
+    let total = total_value(outcome_values.iter().copied());
+    let mut saved = false;
+    if let (Some(save_to), None) = (save_to, &error) {
+        let multiple_choice_forecast = MultipleChoiceForecast {
+            outcomes: Some(outcomes.clone()),
+        };
+        let data = ForecastData::MultipleChoice(multiple_choice_forecast);
+        match save_data(save_to, total, data).await {
+            Ok(()) => saved = true,
+            Err(AppError::Internal(e)) => return Err(AppError::Internal(e)),
+            Err(e) => error = Some(e.to_string()),
+        }
+    }
+    let s = OutcomesTemplate {
+        forecast_id,
+        outcomes: &outcomes,
+        total: &total,
+        error,
+        saved,
+    }
+    .render()?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+}
+
+// Creates the outcomes for a multiple choice forecast, sharing 100% evenly
+// between them.
+pub async fn create_outcomes(
+    database: &Database,
+    id: i64,
+    labels: Vec<String>,
+) -> Result<(), AppError> {
+    let number_of_outcomes = labels.len();
+    if !(MIN_NUMBER_OF_RANGES..=MAX_NUMBER_OF_RANGES).contains(&number_of_outcomes) {
+        return Err(AppError::bad_request(format!(
+            "A forecast needs between {} and {} outcomes, but {} were given",
+            MIN_NUMBER_OF_RANGES, MAX_NUMBER_OF_RANGES, number_of_outcomes
+        )));
+    }
+    validate_labels(&labels).map_err(AppError::bad_request)?;
+    let multiple_choice_forecast = MultipleChoiceForecast {
+        outcomes: Some(get_outcomes(&labels, &uniform_values(number_of_outcomes))),
+    };
+    database
+        .update_data(
+            id,
+            ForecastData::MultipleChoice(multiple_choice_forecast),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Every outcome needs a label, and no two outcomes can share one.
+pub fn validate_labels(labels: &[String]) -> anyhow::Result<()> {
+    for (index, label) in labels.iter().enumerate() {
+        if label.is_empty() {
+            return Err(anyhow::anyhow!("Outcome {} needs a label", index + 1));
+        }
+        if labels[..index].contains(label) {
+            return Err(anyhow::anyhow!(
+                "There is more than one outcome labelled '{}'",
+                label
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn get_outcomes(labels: &[String], outcome_values: &[f64]) -> Vec<Outcome> {
+    let total = total_value(outcome_values.iter().copied());
+    labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| {
+            let this_value = *outcome_values.get(index).unwrap_or(&0.0);
+            Outcome {
+                label: label.to_string(),
+                value: this_value,
+                can_floor: can_floor(total, this_value, 0.0),
+                can_ceil: can_ceil(total),
+            }
+        })
+        .collect()
+}
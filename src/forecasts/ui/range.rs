@@ -1,18 +1,44 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use askama::Template;
 use chrono::{Duration, NaiveDate};
 use log::info;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
+use strum_macros::{Display, EnumString};
 
-use crate::db::{Database, RangeForecast};
+use crate::{
+    db::{Database, ForecastData, ForecastType, RangeForecast, Resolution},
+    error::AppError,
+    AppData,
+};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+use super::{
+    forecast::EditPath,
+    numeric::{render_numeric_ranges, NumericFormData},
+    outcome::{render_outcomes, OutcomeFormData},
+};
+
+// Where a range sits relative to the forecast window. The tails are open
+// ended, so their start and end are both the day just outside the window.
+#[derive(
+    Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default, EnumString, Display, JsonSchema,
+)]
+pub enum RangeKind {
+    BeforeStart,
+    #[default]
+    Within,
+    AfterEnd,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, JsonSchema)]
 pub struct Range {
     pub(crate) start: NaiveDate,
     pub(crate) end: NaiveDate,
+    #[serde(default)]
+    pub(crate) kind: RangeKind,
     pub(crate) label: String,
-    pub(crate) value: i32,
+    pub(crate) value: f64,
     pub(crate) can_ceil: bool,
     pub(crate) can_floor: bool,
 }
@@ -21,6 +47,7 @@ impl std::fmt::Display for Range {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "start_date: {}", self.start)?;
         write!(f, "end_date: {}", self.end)?;
+        write!(f, "kind: {}", self.kind)?;
         write!(f, "label: {}", self.label)?;
         write!(f, "value: {}", self.value)?;
         Ok(())
@@ -30,145 +57,476 @@ impl std::fmt::Display for Range {
 #[derive(Template)]
 #[template(path = "forecasts/_range.html")]
 pub struct RangesTemplate<'a> {
+    pub(crate) forecast_id: &'a str,
     pub(crate) ranges: &'a Vec<Range>,
     pub(crate) start_date: NaiveDate,
     pub(crate) end_date: NaiveDate,
-    pub(crate) total: &'a i32,
+    pub(crate) total: &'a f64,
+    pub(crate) min_value: f64,
+    pub(crate) error: Option<String>,
+    pub(crate) saved: bool,
+    pub(crate) outcome_index: Option<usize>,
+    pub(crate) locked: bool,
+}
+
+impl Range {
+    pub fn is_tail(&self) -> bool {
+        self.kind != RangeKind::Within
+    }
+
+    // Whether this is the range the event actually fell in. Not happening at
+    // all counts towards the range after the end date.
+    pub fn contains_outcome(&self, outcome_date: Option<NaiveDate>) -> bool {
+        match (self.kind, outcome_date) {
+            (RangeKind::AfterEnd, None) => true,
+            (_, None) => false,
+            (RangeKind::BeforeStart, Some(date)) => date <= self.end,
+            (RangeKind::Within, Some(date)) => self.start <= date && date <= self.end,
+            (RangeKind::AfterEnd, Some(date)) => self.start <= date,
+        }
+    }
+}
+
+// The index of the range a resolved forecast's event fell in, if any did.
+pub fn outcome_index(ranges: &[Range], resolution: Option<&Resolution>) -> Option<usize> {
+    let resolution = resolution?;
+    ranges
+        .iter()
+        .position(|range| range.contains_outcome(resolution.outcome_date))
+}
+
+// Which of the optional open-ended ranges either side of the forecast window
+// are in use. When present they come first and last in a forecast's ranges.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TailRanges {
+    pub before_start: bool,
+    pub after_end: bool,
+}
+
+impl TailRanges {
+    pub fn from_ranges(ranges: &[Range]) -> TailRanges {
+        TailRanges {
+            before_start: ranges
+                .first()
+                .is_some_and(|range| range.kind == RangeKind::BeforeStart),
+            after_end: ranges
+                .last()
+                .is_some_and(|range| range.kind == RangeKind::AfterEnd),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.before_start as usize + self.after_end as usize
+    }
+}
+
+pub const MIN_NUMBER_OF_RANGES: usize = 2;
+pub const MAX_NUMBER_OF_RANGES: usize = 50;
+
+// Values are percentages, kept to a tenth of a percent so that unlikely
+// ranges needn't be rounded to nothing.
+const VALUE_PRECISION: f64 = 10.0;
+
+// New date forecasts give every range at least this much, so whichever range
+// the event falls in never scores infinitely badly.
+pub const DEFAULT_MIN_VALUE: f64 = 0.1;
+
+pub fn round_value(value: f64) -> f64 {
+    (value * VALUE_PRECISION).round() / VALUE_PRECISION
+}
+
+// Sums in tenths, so that adding up to 100% is exact.
+pub fn total_value(values: impl IntoIterator<Item = f64>) -> f64 {
+    round_value(values.into_iter().sum())
 }
 
-#[derive(Deserialize)]
+fn to_tenths(value: f64) -> i64 {
+    (value * VALUE_PRECISION).round() as i64
+}
+
+fn from_tenths(tenths: i64) -> f64 {
+    tenths as f64 / VALUE_PRECISION
+}
+
+// The range values are submitted as repeated `range` fields, in order, so a
+// form can carry any number of ranges. This holds for every forecast type
+// that uses ranges; a hidden `forecast_type` field says how to read the rest
+// of the form, defaulting to a date forecast.
+pub fn range_values_from_pairs(pairs: &[(String, String)]) -> Result<Vec<f64>, AppError> {
+    pairs
+        .iter()
+        .filter(|(key, _)| key == "range")
+        .map(|(_, value)| parse_value(value))
+        .collect()
+}
+
+fn parse_value(value: &str) -> Result<f64, AppError> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .map(round_value)
+        .ok_or_else(|| AppError::bad_request(format!("Range value '{}' isn't a number", value)))
+}
+
+// The least each range may hold. Only date forecasts have one.
+fn min_value_from_pairs(pairs: &[(String, String)]) -> Result<f64, AppError> {
+    match pairs.iter().find(|(key, _)| key == "min_value") {
+        Some((_, value)) => parse_value(value),
+        None => Ok(0.0),
+    }
+}
+
+fn forecast_type_from_pairs(pairs: &[(String, String)]) -> Result<ForecastType, AppError> {
+    match pairs.iter().find(|(key, _)| key == "forecast_type") {
+        Some((_, value)) => ForecastType::from_str(value)
+            .map_err(|_| AppError::bad_request(format!("Invalid forecast type '{}'", value))),
+        None => Ok(ForecastType::Date),
+    }
+}
+
+// Looks up a form field that must be there.
+pub fn required_field<'a>(pairs: &'a [(String, String)], name: &str) -> Result<&'a str, AppError> {
+    pairs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+        .ok_or_else(|| AppError::bad_request(format!("No {} supplied", name)))
+}
+
+pub fn parse_date(value: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| AppError::bad_request(format!("'{}' isn't a date like YYYY-MM-DD", value)))
+}
+
+// Each range in a date forecast may carry its own `range_start` and
+// `range_end`; without them the window is split evenly. A `range_kind` per
+// range marks the tails, which have no dates of their own.
 pub struct RangeFormData {
     start_date: NaiveDate,
     end_date: NaiveDate,
-    range_1: i32,
-    range_2: i32,
-    range_3: i32,
-    range_4: i32,
-    range_5: i32,
+    range_bounds: Option<Vec<(NaiveDate, NaiveDate)>>,
+    tails: TailRanges,
 }
 
-pub async fn ceiling(
-    query: web::Query<HashMap<String, String>>,
-    form: web::Form<RangeFormData>,
-) -> Result<HttpResponse> {
-    let index_of_range_to_adjust = query
-        .get("range_index")
-        .expect("No range_index supplied")
-        .parse::<usize>()
-        .expect("Supplied index is not an number");
-    let mut range_values = vec![
-        form.range_1,
-        form.range_2,
-        form.range_3,
-        form.range_4,
-        form.range_5,
-    ];
+impl RangeFormData {
+    pub fn from_pairs(pairs: &[(String, String)]) -> Result<RangeFormData, AppError> {
+        let start_date = parse_date(required_field(pairs, "start_date")?)?;
+        let end_date = parse_date(required_field(pairs, "end_date")?)?;
+        let dates = |name: &str| -> Result<Vec<NaiveDate>, AppError> {
+            pairs
+                .iter()
+                .filter(|(key, _)| key == name)
+                .map(|(_, value)| parse_date(value))
+                .collect()
+        };
+        let range_starts = dates("range_start")?;
+        let range_ends = dates("range_end")?;
+        let range_bounds = if range_starts.is_empty() {
+            None
+        } else {
+            Some(range_starts.into_iter().zip(range_ends).collect())
+        };
+        let range_kinds = pairs
+            .iter()
+            .filter(|(key, _)| key == "range_kind")
+            .map(|(_, value)| {
+                RangeKind::from_str(value)
+                    .map_err(|_| AppError::bad_request(format!("Invalid range kind '{}'", value)))
+            })
+            .collect::<Result<Vec<RangeKind>, AppError>>()?;
+        let tails = TailRanges {
+            before_start: range_kinds.first() == Some(&RangeKind::BeforeStart),
+            after_end: range_kinds.last() == Some(&RangeKind::AfterEnd),
+        };
+        Ok(RangeFormData {
+            start_date,
+            end_date,
+            range_bounds,
+            tails,
+        })
+    }
 
-    let total = range_values.iter().sum::<i32>();
+    // The window is only split evenly once it's known to be long enough.
+    fn range_bounds(&self, number_of_values: usize) -> anyhow::Result<Vec<(NaiveDate, NaiveDate)>> {
+        match &self.range_bounds {
+            Some(range_bounds) => Ok(range_bounds.clone()),
+            None => {
+                let number_of_ranges = number_of_values.saturating_sub(self.tails.count());
+                validate_window(self.start_date, self.end_date, number_of_ranges)?;
+                Ok(equal_bounds(
+                    self.start_date,
+                    self.end_date,
+                    number_of_ranges,
+                ))
+            }
+        }
+    }
+}
 
+// A range can give up some of its value, down to the minimum, when the total
+// is over 100%, and take up the slack when the total is under.
+pub fn can_floor(total: f64, value: f64, min_value: f64) -> bool {
+    total > 100.0 && value > min_value
+}
+
+pub fn can_ceil(total: f64) -> bool {
+    total < 100.0
+}
+
+// The range to adjust is given by its 1-based `range_index`. Values are
+// only adjusted once they're all valid, so the arithmetic can't overflow.
+fn range_index(
+    query: &HashMap<String, String>,
+    range_values: &[f64],
+    min_value: f64,
+) -> anyhow::Result<usize> {
+    let range_index = query
+        .get("range_index")
+        .ok_or_else(|| anyhow::anyhow!("No range_index supplied"))?;
+    validate_values(range_values, min_value)?;
     // TODO: index ranges by 0-based index, not 1-based index
-    let value_to_adjust = range_values.get(index_of_range_to_adjust - 1).unwrap();
-    let ceilinged_value = value_to_adjust + (100 - total);
+    range_index
+        .parse::<usize>()
+        .ok()
+        .and_then(|range_index| range_index.checked_sub(1))
+        .filter(|index| *index < range_values.len())
+        .ok_or_else(|| anyhow::anyhow!("There is no range {}", range_index))
+}
 
-    range_values[index_of_range_to_adjust - 1] = ceilinged_value;
+pub async fn ceiling(
+    query: web::Query<HashMap<String, String>>,
+    form: web::Form<Vec<(String, String)>>,
+) -> Result<HttpResponse, AppError> {
+    let mut range_values = range_values_from_pairs(&form)?;
+    let min_value = min_value_from_pairs(&form)?;
+    let error = match range_index(&query, &range_values, min_value) {
+        Ok(index) => {
+            let total = total_value(range_values.iter().copied());
+            range_values[index] =
+                round_value(range_values[index] + 100.0 - total).clamp(min_value, 100.0);
+            None
+        }
+        Err(e) => Some(e.to_string()),
+    };
 
-    let ranges = get_ranges(form.start_date, form.end_date, &range_values);
-    let s = RangesTemplate {
-        ranges: &ranges,
-        start_date: form.start_date,
-        end_date: form.end_date,
-        total: &range_values.iter().sum(),
-    }
-    .render()
-    .unwrap();
-    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+    render_ranges(&form, &range_values, None, error).await
 }
 
 pub async fn floor(
     query: web::Query<HashMap<String, String>>,
-    form: web::Form<RangeFormData>,
-) -> Result<HttpResponse> {
-    let index_of_range_to_adjust = query
-        .get("range_index")
-        .expect("No range_index supplied")
-        .parse::<usize>()
-        .expect("Supplied index is not an number");
-    let mut range_values = vec![
-        form.range_1,
-        form.range_2,
-        form.range_3,
-        form.range_4,
-        form.range_5,
-    ];
+    form: web::Form<Vec<(String, String)>>,
+) -> Result<HttpResponse, AppError> {
+    let mut range_values = range_values_from_pairs(&form)?;
+    let min_value = min_value_from_pairs(&form)?;
+    let error = match range_index(&query, &range_values, min_value) {
+        Ok(index) => {
+            let total = total_value(range_values.iter().copied());
+            range_values[index] = round_value(range_values[index] - (total - 100.0)).max(min_value);
+            None
+        }
+        Err(e) => Some(e.to_string()),
+    };
 
-    let total = range_values.iter().sum::<i32>();
+    render_ranges(&form, &range_values, None, error).await
+}
 
-    // TODO: index ranges by 0-based index, not 1-based index
-    let value_to_adjust = range_values.get(index_of_range_to_adjust - 1).unwrap();
-    let mut new_value = value_to_adjust - (total - 100);
-    if new_value < 0 {
-        new_value = 0;
+// Scales every range so they add up to 100%.
+pub async fn normalize(form: web::Form<Vec<(String, String)>>) -> Result<HttpResponse, AppError> {
+    let range_values = range_values_from_pairs(&form)?;
+    let min_value = min_value_from_pairs(&form)?;
+    match validate_values(&range_values, 0.0)
+        .and_then(|()| validate_min_value(min_value, range_values.len()))
+    {
+        Ok(()) => {
+            let range_values = normalized_values(&range_values, min_value);
+            render_ranges(&form, &range_values, None, None).await
+        }
+        Err(e) => render_ranges(&form, &range_values, None, Some(e.to_string())).await,
     }
+}
 
-    range_values[index_of_range_to_adjust - 1] = new_value;
+// Shares 100% evenly between the ranges again.
+pub async fn reset(form: web::Form<Vec<(String, String)>>) -> Result<HttpResponse, AppError> {
+    let range_values = range_values_from_pairs(&form)?;
+    let error = (range_values.is_empty()).then(|| "There are no ranges to reset".to_string());
+    let range_values = match error {
+        Some(_) => range_values,
+        None => uniform_values(range_values.len()),
+    };
+    render_ranges(&form, &range_values, None, error).await
+}
 
-    let ranges = get_ranges(form.start_date, form.end_date, &range_values);
-    let s = RangesTemplate {
-        ranges: &ranges,
-        start_date: form.start_date,
-        end_date: form.end_date,
-        total: &range_values.iter().sum(),
+// Updates the values of some ranges from a manual adjustment
+pub async fn update_ranges(
+    query: web::Query<Vec<(String, String)>>,
+) -> Result<HttpResponse, AppError> {
+    render_ranges(&query, &range_values_from_pairs(&query)?, None, None).await
+}
+
+// Saves the ranges as they are in the form, provided they're valid and add
+// up to 100%, so they're there when the forecast is next loaded.
+pub async fn save_ranges(
+    path: web::Path<EditPath>,
+    form: web::Form<Vec<(String, String)>>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    match database.read_by_id(path.id).await? {
+        Some(forecast) if forecast.forecast_type == forecast_type_from_pairs(&form)? => (),
+        Some(_) => {
+            return Err(AppError::bad_request(
+                "Ranges don't match the forecast type",
+            ));
+        }
+        None => return Err(AppError::no_forecast(path.id)),
     }
-    .render()
-    .unwrap();
-    Ok(HttpResponse::Ok().content_type("text/html").body(s))
+    let save_to = SaveTo {
+        database,
+        id: path.id,
+        note: form
+            .iter()
+            .find(|(key, _)| key == "note")
+            .map(|(_, note)| note.trim().to_string())
+            .filter(|note| !note.is_empty()),
+    };
+    render_ranges(
+        &form,
+        &range_values_from_pairs(&form)?,
+        Some(&save_to),
+        None,
+    )
+    .await
 }
 
-// Updates the values of some ranges from a manual adjustment
-pub async fn update_ranges(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
-    let start_date_str = query.get("start_date").unwrap();
-    let end_date_str = query.get("end_date").unwrap();
-    let start_date = NaiveDate::parse_from_str(start_date_str, "%Y-%m-%d").unwrap();
-    let end_date = NaiveDate::parse_from_str(end_date_str, "%Y-%m-%d").unwrap();
-
-    let range_values = vec![
-        query
-            .get("range_1")
-            .unwrap()
-            .parse::<i32>()
-            .expect("Range should have a numerical value"),
-        query
-            .get("range_2")
-            .unwrap()
-            .parse::<i32>()
-            .expect("Range should have a numerical value"),
-        query
-            .get("range_3")
-            .unwrap()
-            .parse::<i32>()
-            .expect("Range should have a numerical value"),
-        query
-            .get("range_4")
-            .unwrap()
-            .parse::<i32>()
-            .expect("Range should have a numerical value"),
-        query
-            .get("range_5")
-            .unwrap()
-            .parse::<i32>()
-            .expect("Range should have a numerical value"),
-    ];
-
-    let ranges = get_ranges(start_date, end_date, &range_values);
+// Where to save ranges as they're re-rendered, and the note to keep with the
+// revision.
+pub struct SaveTo<'a> {
+    pub database: &'a Database,
+    pub id: i64,
+    pub note: Option<String>,
+}
+
+// Saving is refused unless the ranges make up a whole probability
+// distribution.
+pub async fn save_data(
+    save_to: &SaveTo<'_>,
+    total: f64,
+    data: ForecastData,
+) -> Result<(), AppError> {
+    if to_tenths(total) != to_tenths(100.0) {
+        return Err(AppError::bad_request(format!(
+            "The ranges need to add up to 100% before they can be saved, but they add up to {}%",
+            total
+        )));
+    }
+    save_to
+        .database
+        .update_data(save_to.id, data, save_to.note.clone())
+        .await?;
+    Ok(())
+}
+
+// Every range endpoint ends up here. Values outside 0-100% are shown clamped,
+// along with what was wrong with them, and nothing is saved while there's an
+// error to show.
+async fn render_ranges(
+    pairs: &[(String, String)],
+    range_values: &[f64],
+    save_to: Option<&SaveTo<'_>>,
+    error: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let min_value = min_value_from_pairs(pairs)?;
+    let error = error.or_else(|| {
+        validate_min_value(min_value, range_values.len())
+            .and_then(|()| validate_values(range_values, min_value))
+            .err()
+            .map(|e| e.to_string())
+    });
+    let range_values: Vec<f64> = range_values
+        .iter()
+        .map(|value| value.clamp(0.0, 100.0))
+        .collect();
+    let range_values = range_values.as_slice();
+    let forecast_id = match save_to {
+        Some(save_to) => save_to.id.to_string(),
+        None => pairs
+            .iter()
+            .find(|(key, _)| key == "forecast_id")
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_default(),
+    };
+    match forecast_type_from_pairs(pairs)? {
+        ForecastType::Date => {
+            let form = RangeFormData::from_pairs(pairs)?;
+            render_date_ranges(&form, range_values, min_value, &forecast_id, save_to, error).await
+        }
+        ForecastType::Numeric => {
+            let form = NumericFormData::from_pairs(pairs)?;
+            render_numeric_ranges(&form, range_values, &forecast_id, save_to, error).await
+        }
+        ForecastType::MultipleChoice => {
+            let form = OutcomeFormData::from_pairs(pairs)?;
+            render_outcomes(&form, range_values, &forecast_id, save_to, error).await
+        }
+        ForecastType::Binary => Err(AppError::bad_request("Binary forecasts don't have ranges")),
+    }
+}
 
+// Re-renders the ranges partial. Boundaries that don't tile the forecast
+// window are still shown, so they can be corrected, along with the reason.
+async fn render_date_ranges(
+    form: &RangeFormData,
+    range_values: &[f64],
+    min_value: f64,
+    forecast_id: &str,
+    save_to: Option<&SaveTo<'_>>,
+    error: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let (range_bounds, bounds_error) = match form.range_bounds(range_values.len()) {
+        Ok(range_bounds) => {
+            let bounds_error = validate_window(form.start_date, form.end_date, range_bounds.len())
+                .and_then(|()| validate_bounds(form.start_date, form.end_date, &range_bounds))
+                .and_then(|()| {
+                    validate_count(range_values, range_bounds.len() + form.tails.count())
+                })
+                .err();
+            (range_bounds, bounds_error)
+        }
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let mut error = error.or_else(|| bounds_error.map(|e| e.to_string()));
+    let ranges = get_ranges(&range_bounds, range_values, form.tails, min_value);
+    let total = total_value(range_values.iter().copied());
+    let mut saved = false;
+    if let (Some(save_to), None) = (save_to, &error) {
+        let range_forecast = RangeForecast {
+            start_date: Some(form.start_date.to_string()),
+            end_date: Some(form.end_date.to_string()),
+            number_of_ranges: Some(range_bounds.len()),
+            min_value: Some(min_value),
+            ranges: Some(ranges.clone()),
+            distribution: None,
+        };
+        match save_data(save_to, total, ForecastData::Date(range_forecast)).await {
+            Ok(()) => saved = true,
+            Err(AppError::Internal(e)) => return Err(AppError::Internal(e)),
+            Err(e) => error = Some(e.to_string()),
+        }
+    }
     let s = RangesTemplate {
+        forecast_id,
         ranges: &ranges,
-        start_date,
-        end_date,
-        total: &range_values.iter().sum(),
+        start_date: form.start_date,
+        end_date: form.end_date,
+        total: &total,
+        min_value,
+        error,
+        saved,
+        outcome_index: None,
+        locked: false,
     }
-    .render()
-    .unwrap();
+    .render()?;
     Ok(HttpResponse::Ok().content_type("text/html").body(s))
 }
 
@@ -178,45 +536,505 @@ pub async fn create_ranges(
     id: i64,
     start_date: NaiveDate,
     end_date: NaiveDate,
-) -> anyhow::Result<()> {
+    range_bounds: Vec<(NaiveDate, NaiveDate)>,
+    tails: TailRanges,
+    min_value: f64,
+) -> Result<(), AppError> {
+    let number_of_ranges = range_bounds.len();
+    validate_window(start_date, end_date, number_of_ranges)
+        .and_then(|()| validate_bounds(start_date, end_date, &range_bounds))
+        .and_then(|()| validate_min_value(min_value, number_of_ranges + tails.count()))
+        .map_err(AppError::bad_request)?;
     let range_forecast = RangeForecast {
         start_date: Some(start_date.to_string()),
         end_date: Some(end_date.to_string()),
-        ranges: Some(get_ranges(start_date, end_date, &[20, 20, 20, 20, 20])),
+        number_of_ranges: Some(number_of_ranges),
+        min_value: Some(min_value),
+        ranges: Some(get_ranges(
+            &range_bounds,
+            &uniform_values(number_of_ranges + tails.count()),
+            tails,
+            min_value,
+        )),
+        distribution: None,
     };
-    database.update_data(id, range_forecast).await?;
+    database
+        .update_data(id, ForecastData::Date(range_forecast), None)
+        .await?;
     Ok(())
 }
 
-fn get_ranges(start_date: NaiveDate, end_date: NaiveDate, range_values: &[i32]) -> Vec<Range> {
-    let days_in_range = end_date.signed_duration_since(start_date).num_days();
-    let number_of_ranges = 5;
-    let range_size = days_in_range / number_of_ranges;
-    let mut ranges: Vec<Range> = Vec::new();
-    let mut range_start_date = start_date;
-    let total: i32 = range_values.iter().sum();
+// Splits 100% as evenly as tenths of a percent allow, giving any remainder to
+// the earliest ranges.
+pub fn uniform_values(number_of_ranges: usize) -> Vec<f64> {
+    let number_of_ranges = number_of_ranges as i64;
+    let base = to_tenths(100.0) / number_of_ranges;
+    let remainder = to_tenths(100.0) % number_of_ranges;
+    (0..number_of_ranges)
+        .map(|index| from_tenths(if index < remainder { base + 1 } else { base }))
+        .collect()
+}
 
+// Splits the window, inclusive of both ends, into ranges of equal length.
+// Any remainder days go to the earliest ranges so nothing is dropped.
+pub fn equal_bounds(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    number_of_ranges: usize,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let days_in_window = (end_date - start_date).num_days() + 1;
+    let number_of_ranges = number_of_ranges as i64;
+    let range_size = days_in_window / number_of_ranges;
+    let remainder = days_in_window % number_of_ranges;
+    let mut range_bounds = Vec::new();
+    let mut range_start_date = start_date;
     for index in 0..number_of_ranges {
-        let range_end_date = range_start_date
-            .checked_add_signed(Duration::days(range_size))
-            .unwrap();
-        let days = (range_end_date - range_start_date).num_days();
-        let this_value = *range_values.get(index as usize).unwrap_or(&0);
-        let can_floor = total > 100 && this_value > 0;
-        let can_ceil = total < 100;
+        let days = if index < remainder {
+            range_size + 1
+        } else {
+            range_size
+        };
+        let range_end_date = range_start_date + Duration::days(days - 1);
+        range_bounds.push((range_start_date, range_end_date));
+        range_start_date = match range_end_date.succ_opt() {
+            Some(next_start_date) => next_start_date,
+            None => break,
+        };
+    }
+    range_bounds
+}
+
+// Builds bounds from the last day of each range but the final one, which
+// always runs to the end of the window.
+pub fn bounds_from_end_dates(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    range_end_dates: &[NaiveDate],
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut range_bounds = Vec::new();
+    let mut range_start_date = start_date;
+    for range_end_date in range_end_dates.iter().chain(std::iter::once(&end_date)) {
+        range_bounds.push((range_start_date, *range_end_date));
+        range_start_date = match range_end_date.succ_opt() {
+            Some(next_start_date) => next_start_date,
+            None => break,
+        };
+    }
+    range_bounds
+}
+
+// Every value is a percentage, no less than the minimum.
+pub fn validate_values(range_values: &[f64], min_value: f64) -> anyhow::Result<()> {
+    for (index, value) in range_values.iter().enumerate() {
+        if !(min_value..=100.0).contains(value) {
+            return Err(anyhow::anyhow!(
+                "Range {} is {}%, but it must be between {}% and 100%",
+                index + 1,
+                value,
+                min_value
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Every range holding the minimum can't take more than 100% between them.
+pub fn validate_min_value(min_value: f64, number_of_ranges: usize) -> anyhow::Result<()> {
+    if min_value < 0.0 {
+        return Err(anyhow::anyhow!(
+            "The least a range can hold is {}%, which is below 0%",
+            min_value
+        ));
+    }
+    if to_tenths(min_value) * number_of_ranges as i64 > to_tenths(100.0) {
+        return Err(anyhow::anyhow!(
+            "{} ranges of at least {}% each add up to more than 100%",
+            number_of_ranges,
+            min_value
+        ));
+    }
+    Ok(())
+}
+
+// There must be a value for every range, tails included.
+pub fn validate_count(range_values: &[f64], number_of_ranges: usize) -> anyhow::Result<()> {
+    if range_values.len() != number_of_ranges {
+        return Err(anyhow::anyhow!(
+            "There are {} ranges but {} values for them",
+            number_of_ranges,
+            range_values.len()
+        ));
+    }
+    Ok(())
+}
+
+// The forecast window must end after it starts, and be at least a day long
+// for each of the ranges it's split into.
+pub fn validate_window(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    number_of_ranges: usize,
+) -> anyhow::Result<()> {
+    if end_date <= start_date {
+        return Err(anyhow::anyhow!(
+            "The forecast ends on {}, which isn't after it starts on {}",
+            end_date,
+            start_date
+        ));
+    }
+    if !(MIN_NUMBER_OF_RANGES..=MAX_NUMBER_OF_RANGES).contains(&number_of_ranges) {
+        return Err(anyhow::anyhow!(
+            "A forecast needs between {} and {} ranges, but it has {}",
+            MIN_NUMBER_OF_RANGES,
+            MAX_NUMBER_OF_RANGES,
+            number_of_ranges
+        ));
+    }
+    let days_in_window = (end_date - start_date).num_days() + 1;
+    if days_in_window < number_of_ranges as i64 {
+        return Err(anyhow::anyhow!(
+            "{} days can't be split into {} ranges of at least a day each",
+            days_in_window,
+            number_of_ranges
+        ));
+    }
+    Ok(())
+}
+
+// Ranges must run back to back, without gaps or overlaps, from the first day
+// of the forecast window to the last.
+pub fn validate_bounds(
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    range_bounds: &[(NaiveDate, NaiveDate)],
+) -> anyhow::Result<()> {
+    let (first, last) = match (range_bounds.first(), range_bounds.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(anyhow::anyhow!("A forecast needs at least one range")),
+    };
+    if first.0 != start_date {
+        return Err(anyhow::anyhow!(
+            "The first range starts on {} but the forecast starts on {}",
+            first.0,
+            start_date
+        ));
+    }
+    if last.1 != end_date {
+        return Err(anyhow::anyhow!(
+            "The last range ends on {} but the forecast ends on {}",
+            last.1,
+            end_date
+        ));
+    }
+    for (range_start, range_end) in range_bounds {
+        if range_end < range_start {
+            return Err(anyhow::anyhow!(
+                "The range starting on {} ends before it starts",
+                range_start
+            ));
+        }
+    }
+    for pair in range_bounds.windows(2) {
+        let (previous_end, next_start) = (pair[0].1, pair[1].0);
+        if next_start <= previous_end {
+            return Err(anyhow::anyhow!(
+                "The range starting on {} overlaps the one before it",
+                next_start
+            ));
+        }
+        if Some(next_start) > previous_end.succ_opt() {
+            return Err(anyhow::anyhow!(
+                "There is a gap between {} and {}",
+                previous_end,
+                next_start
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Builds the ranges, tails included, from the window's range bounds. The
+// values run in the same order as the ranges: earliest tail first.
+pub fn get_ranges(
+    range_bounds: &[(NaiveDate, NaiveDate)],
+    range_values: &[f64],
+    tails: TailRanges,
+    min_value: f64,
+) -> Vec<Range> {
+    let mut ranges: Vec<Range> = Vec::new();
+    let total = total_value(range_values.iter().copied());
+    let mut values = range_values.iter().copied();
+    let mut next_range = |start: NaiveDate, end: NaiveDate, kind: RangeKind, label: String| {
+        let this_value = values.next().unwrap_or(0.0);
+        let can_floor = can_floor(total, this_value, min_value);
+        let can_ceil = can_ceil(total);
         info!("{}, {}, {}, {}", total, this_value, can_floor, can_ceil);
-        let label = format!("{range_start_date} - {range_end_date} ({days} days)").to_string();
-        ranges.push(Range {
-            start: range_start_date,
-            end: range_end_date,
+        Range {
+            start,
+            end,
+            kind,
             label,
             value: this_value,
             can_floor,
             can_ceil,
-        });
-        range_start_date = range_end_date
-            .checked_add_signed(Duration::days(1))
-            .unwrap();
+        }
+    };
+
+    // The tails sit just outside the window, unless it's at the very edge of
+    // the calendar.
+    if let (true, Some((start_date, _))) = (tails.before_start, range_bounds.first()) {
+        let day_before = start_date.pred_opt().unwrap_or(*start_date);
+        let label = format!("Before {start_date}");
+        ranges.push(next_range(
+            day_before,
+            day_before,
+            RangeKind::BeforeStart,
+            label,
+        ));
+    }
+    for (range_start_date, range_end_date) in range_bounds {
+        let days = (*range_end_date - *range_start_date).num_days() + 1;
+        let label = format!("{range_start_date} - {range_end_date} ({days} days)").to_string();
+        ranges.push(next_range(
+            *range_start_date,
+            *range_end_date,
+            RangeKind::Within,
+            label,
+        ));
+    }
+    if let (true, Some((_, end_date))) = (tails.after_end, range_bounds.last()) {
+        let day_after = end_date.succ_opt().unwrap_or(*end_date);
+        let label = format!("After {end_date}, or never");
+        ranges.push(next_range(day_after, day_after, RangeKind::AfterEnd, label));
     }
     ranges
 }
+
+// Scales the values in proportion so they add up to exactly 100%, working in
+// tenths of a percent. Every value keeps the minimum and the rest is shared
+// in proportion to how far each was above it. Rounding down leaves a few
+// tenths over, which go to the values that lost the most to rounding,
+// earliest first on a tie, so the same values always normalize the same way.
+// Values that are all at the minimum become uniform.
+pub fn normalized_values(range_values: &[f64], min_value: f64) -> Vec<f64> {
+    let min_tenths = to_tenths(min_value);
+    let excess: Vec<i64> = range_values
+        .iter()
+        .map(|value| (to_tenths(*value) - min_tenths).max(0))
+        .collect();
+    let total_excess: i64 = excess.iter().sum();
+    if total_excess <= 0 {
+        return uniform_values(range_values.len());
+    }
+    let to_share = to_tenths(100.0) - min_tenths * range_values.len() as i64;
+    let mut shares: Vec<i64> = excess
+        .iter()
+        .map(|excess| excess * to_share / total_excess)
+        .collect();
+    let mut by_remainder: Vec<usize> = (0..range_values.len()).collect();
+    by_remainder.sort_by_key(|index| std::cmp::Reverse(excess[*index] * to_share % total_excess));
+    let left_over = to_share - shares.iter().sum::<i64>();
+    for index in by_remainder.into_iter().take(left_over as usize) {
+        shares[index] += 1;
+    }
+    shares
+        .into_iter()
+        .map(|share| from_tenths(min_tenths + share))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn bounds(pairs: &[(&str, &str)]) -> Vec<(NaiveDate, NaiveDate)> {
+        pairs
+            .iter()
+            .map(|(start, end)| (date(start), date(end)))
+            .collect()
+    }
+
+    #[test]
+    fn equal_bounds_gives_remainder_days_to_the_earliest_ranges() {
+        let range_bounds = equal_bounds(date("2022-06-01"), date("2022-06-30"), 4);
+        assert_eq!(
+            range_bounds,
+            bounds(&[
+                ("2022-06-01", "2022-06-08"),
+                ("2022-06-09", "2022-06-16"),
+                ("2022-06-17", "2022-06-23"),
+                ("2022-06-24", "2022-06-30"),
+            ])
+        );
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds).is_ok());
+    }
+
+    #[test]
+    fn equal_bounds_splits_an_even_window_evenly() {
+        let range_bounds = equal_bounds(date("2022-06-01"), date("2022-06-30"), 3);
+        assert_eq!(
+            range_bounds,
+            bounds(&[
+                ("2022-06-01", "2022-06-10"),
+                ("2022-06-11", "2022-06-20"),
+                ("2022-06-21", "2022-06-30"),
+            ])
+        );
+    }
+
+    #[test]
+    fn bounds_from_end_dates_runs_the_last_range_to_the_end() {
+        let range_bounds = bounds_from_end_dates(
+            date("2022-06-01"),
+            date("2022-06-30"),
+            &[date("2022-06-07"), date("2022-06-20")],
+        );
+        assert_eq!(
+            range_bounds,
+            bounds(&[
+                ("2022-06-01", "2022-06-07"),
+                ("2022-06-08", "2022-06-20"),
+                ("2022-06-21", "2022-06-30"),
+            ])
+        );
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds).is_ok());
+    }
+
+    #[test]
+    fn bounds_from_unsorted_end_dates_are_rejected() {
+        let range_bounds = bounds_from_end_dates(
+            date("2022-06-01"),
+            date("2022-06-30"),
+            &[date("2022-06-20"), date("2022-06-07")],
+        );
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds).is_err());
+    }
+
+    #[test]
+    fn validate_bounds_rejects_a_gap() {
+        let range_bounds = bounds(&[("2022-06-01", "2022-06-10"), ("2022-06-12", "2022-06-30")]);
+        let error = validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "There is a gap between 2022-06-10 and 2022-06-12"
+        );
+    }
+
+    #[test]
+    fn validate_bounds_rejects_an_overlap() {
+        let range_bounds = bounds(&[("2022-06-01", "2022-06-10"), ("2022-06-10", "2022-06-30")]);
+        let error = validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "The range starting on 2022-06-10 overlaps the one before it"
+        );
+    }
+
+    #[test]
+    fn validate_bounds_rejects_unsorted_ranges() {
+        let range_bounds = bounds(&[
+            ("2022-06-01", "2022-06-10"),
+            ("2022-06-21", "2022-06-30"),
+            ("2022-06-11", "2022-06-20"),
+        ]);
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds).is_err());
+    }
+
+    #[test]
+    fn validate_bounds_rejects_a_range_that_ends_before_it_starts() {
+        let range_bounds = bounds(&[("2022-06-01", "2022-06-10"), ("2022-06-20", "2022-06-11")]);
+        let error = validate_bounds(date("2022-06-01"), date("2022-06-11"), &range_bounds);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "The range starting on 2022-06-20 ends before it starts"
+        );
+    }
+
+    #[test]
+    fn validate_bounds_rejects_bounds_that_miss_the_window() {
+        let range_bounds = bounds(&[("2022-06-02", "2022-06-15"), ("2022-06-16", "2022-06-30")]);
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds).is_err());
+        let range_bounds = bounds(&[("2022-06-01", "2022-06-15"), ("2022-06-16", "2022-06-29")]);
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &range_bounds).is_err());
+        assert!(validate_bounds(date("2022-06-01"), date("2022-06-30"), &[]).is_err());
+    }
+
+    // Adds up in whole tenths, so a total of exactly 100% can be checked.
+    fn tenths_total(values: &[f64]) -> i64 {
+        values.iter().map(|value| to_tenths(*value)).sum()
+    }
+
+    #[test]
+    fn normalized_values_scale_in_proportion() {
+        assert_eq!(
+            normalized_values(&[50.0, 30.0, 40.0], 0.0),
+            vec![41.7, 25.0, 33.3]
+        );
+    }
+
+    #[test]
+    fn normalized_values_always_total_exactly_100() {
+        for (values, min_value) in [
+            (vec![50.0, 30.0, 40.0], 0.0),
+            (vec![90.0, 5.0, 5.0, 0.0], 0.5),
+            (vec![12.3, 45.6, 78.9, 0.1, 3.3, 9.9, 27.0], 0.1),
+            (vec![1.0; 49], 0.2),
+            (vec![100.0, 100.0, 100.0], 1.0),
+        ] {
+            let normalized = normalized_values(&values, min_value);
+            assert_eq!(tenths_total(&normalized), 1000, "{:?}", normalized);
+            assert_eq!(total_value(normalized.iter().copied()), 100.0);
+            assert_eq!(normalized.len(), values.len());
+        }
+    }
+
+    #[test]
+    fn normalized_values_keep_the_minimum() {
+        let normalized = normalized_values(&[90.0, 5.0, 5.0, 0.0], 0.5);
+        assert_eq!(normalized, vec![89.5, 5.0, 5.0, 0.5]);
+        let normalized = normalized_values(&[0.0, 50.0, 50.0], 1.0);
+        assert!(
+            normalized.iter().all(|value| *value >= 1.0),
+            "{:?}",
+            normalized
+        );
+        assert_eq!(tenths_total(&normalized), 1000);
+    }
+
+    #[test]
+    fn normalized_values_break_rounding_ties_earliest_first() {
+        assert_eq!(
+            normalized_values(&[1.0, 1.0, 1.0], 0.0),
+            vec![33.4, 33.3, 33.3]
+        );
+        assert_eq!(
+            normalized_values(&[10.0; 6], 0.0),
+            vec![16.7, 16.7, 16.7, 16.7, 16.6, 16.6]
+        );
+    }
+
+    #[test]
+    fn normalized_values_all_at_the_minimum_become_uniform() {
+        assert_eq!(normalized_values(&[0.1, 0.1, 0.1], 0.1), uniform_values(3));
+        assert_eq!(normalized_values(&[0.0, 0.0], 0.0), vec![50.0, 50.0]);
+    }
+
+    #[test]
+    fn uniform_values_give_the_remainder_to_the_earliest() {
+        assert_eq!(uniform_values(3), vec![33.4, 33.3, 33.3]);
+        assert_eq!(uniform_values(4), vec![25.0; 4]);
+        for number_of_ranges in MIN_NUMBER_OF_RANGES..=MAX_NUMBER_OF_RANGES {
+            let values = uniform_values(number_of_ranges);
+            assert_eq!(tenths_total(&values), 1000);
+            let (max, min) = (
+                values.iter().cloned().fold(f64::MIN, f64::max),
+                values.iter().cloned().fold(f64::MAX, f64::min),
+            );
+            assert!(to_tenths(max) - to_tenths(min) <= 1, "{:?}", values);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use actix_web::{web, HttpResponse};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::{
+    db::{ForecastData, ForecastType, RangeForecast},
+    error::AppError,
+    forecasts::score::{score_ranges, time_weighted_score},
+    AppData,
+};
+
+use super::forecast::EditPath;
+
+#[derive(Deserialize)]
+pub struct ResolveFormData {
+    // Empty when the event didn't happen.
+    #[serde(default)]
+    outcome_date: String,
+    not_happened: Option<String>,
+}
+
+// Records what actually happened to a date forecast and scores it, which
+// locks its ranges. Resolving it again corrects the resolution and its scores.
+pub async fn resolve(
+    path: web::Path<EditPath>,
+    form: web::Form<ResolveFormData>,
+    app_data: web::Data<AppData>,
+) -> Result<HttpResponse, AppError> {
+    let database = &app_data.database;
+    // Correcting a resolution keeps the time it was first resolved.
+    let (ranges, resolved_at) = match database.read_by_id(path.id).await? {
+        Some(forecast) if forecast.forecast_type == ForecastType::Date => match forecast.data {
+            Some(ForecastData::Date(RangeForecast {
+                ranges: Some(ranges),
+                ..
+            })) => (
+                ranges,
+                forecast.resolution.map(|resolution| resolution.resolved_at),
+            ),
+            _ => return Err(AppError::bad_request("Create the ranges before resolving")),
+        },
+        Some(_) => {
+            return Err(AppError::bad_request("Only date forecasts can be resolved"));
+        }
+        None => return Err(AppError::no_forecast(path.id)),
+    };
+    let outcome_date = if form.not_happened.is_some() {
+        None
+    } else {
+        match NaiveDate::parse_from_str(form.outcome_date.trim(), "%Y-%m-%d") {
+            Ok(date) => Some(date),
+            Err(_) => {
+                return Err(AppError::bad_request(
+                    "Give the date it happened as YYYY-MM-DD, or say it didn't happen",
+                ));
+            }
+        }
+    };
+    let scores = score_ranges(&ranges, outcome_date);
+    let revisions = database.find_revisions(path.id).await?;
+    let resolved_at = resolved_at.unwrap_or_else(|| Utc::now().naive_utc());
+    let time_weighted_scores = time_weighted_score(&revisions, resolved_at, outcome_date);
+    database
+        .resolve(path.id, outcome_date, scores, time_weighted_scores)
+        .await?;
+    Ok(HttpResponse::SeeOther()
+        .append_header(("location", format!("/forecast/{}", path.id)))
+        .finish())
+}
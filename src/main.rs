@@ -1,18 +1,30 @@
 use std::{env, str::FromStr};
 
 use actix_files as fs;
-use actix_web::{middleware, web, App, HttpResponse, HttpServer, Result};
+use actix_web::{http::StatusCode, middleware, web, App, HttpResponse, HttpServer, Result};
 use db::Database;
+use error::tailor_error;
 use forecasts::ui::{
-    forecast::{create, edit},
-    list::list,
-    range::{ceiling, floor, update_ranges},
+    binary::update_binary,
+    calibration::calibration,
+    diff::diff,
+    distribution::fit_distribution,
+    forecast::{create, create_forecast, edit},
+    history::{history, revision},
+    list::{delete, list},
+    metadata::{edit_metadata, metadata, update},
+    range::{ceiling, floor, normalize, reset, save_ranges, update_ranges},
+    resolution::resolve,
 };
 use log::info;
 
-use crate::forecasts::routes::generate_ranges;
+use crate::forecasts::{
+    api, openapi,
+    routes::{generate_numeric_ranges, generate_outcomes, generate_ranges},
+};
 
 mod db;
+mod error;
 mod forecasts;
 
 // async fn index(
@@ -61,6 +73,7 @@ pub struct AppData {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let logging_level = env::var("LOGGING_LEVEL").expect("LOGGING_LEVEL must be set");
+    #[allow(clippy::expect_fun_call)]
     let level = log::Level::from_str(&logging_level)
         .expect(format!("LOGGING_LEVEL must be parseable, but was {}", logging_level).as_str())
         .to_level_filter();
@@ -78,6 +91,13 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(web::Data::new(app_data.clone()))
             .wrap(middleware::Logger::default())
+            .wrap(
+                middleware::ErrorHandlers::new()
+                    .handler(StatusCode::BAD_REQUEST, tailor_error)
+                    .handler(StatusCode::NOT_FOUND, tailor_error)
+                    .handler(StatusCode::CONFLICT, tailor_error)
+                    .handler(StatusCode::INTERNAL_SERVER_ERROR, tailor_error),
+            )
             // We reason about browser paths and async paths in different ways.
             // For example it's pretty obvious that in a browser we might go to
             // /forecasts/1 to load forecast with id=1. There are a limited number
@@ -93,19 +113,89 @@ async fn main() -> std::io::Result<()> {
             // There's got to be a lot of dicipline to keep this naming straight. I'm not
             // a fan of having to do this but I've not worked out an alternative yet.
             .service(web::resource("/").route(web::get().to(index)))
-            .service(web::resource("/forecast/create").route(web::get().to(create)))
-            .service(web::resource("/forecast/list").route(web::get().to(list))) // TODO: Call this mini-list
-            .service(web::resource("/forecast/{id}").route(web::get().to(edit)))
             .service(
-                web::resource("/forecast/{id}/_generate_ranges")
-                    .route(web::get().to(generate_ranges)),
+                web::resource("/forecast/create")
+                    .route(web::get().to(create))
+                    .route(web::post().to(create_forecast)),
             )
+            .service(web::resource("/forecast/calibration").route(web::get().to(calibration)))
+            .service(web::resource("/forecast/list").route(web::get().to(list))) // TODO: Call this mini-list
+            // These have to come before /forecast/{id}, which would otherwise
+            // match them.
             .service(
                 web::resource("/forecast/_generate_ranges").route(web::get().to(generate_ranges)),
             )
             .service(web::resource("/forecast/_update_ranges").route(web::get().to(update_ranges)))
             .service(web::resource("/forecast/_ceiling_range").route(web::post().to(ceiling)))
             .service(web::resource("/forecast/_floor_range").route(web::post().to(floor)))
+            .service(web::resource("/forecast/_normalize_ranges").route(web::post().to(normalize)))
+            .service(web::resource("/forecast/_reset_ranges").route(web::post().to(reset)))
+            .service(
+                web::resource("/forecast/{id}")
+                    .route(web::get().to(edit))
+                    .route(web::post().to(update))
+                    .route(web::delete().to(delete)),
+            )
+            .service(web::resource("/forecast/{id}/history").route(web::get().to(history)))
+            .service(web::resource("/forecast/{id}/diff").route(web::get().to(diff)))
+            .service(
+                web::resource("/forecast/{id}/history/{revision_id}")
+                    .route(web::get().to(revision)),
+            )
+            .service(
+                web::resource("/forecast/{id}/_generate_ranges")
+                    .route(web::get().to(generate_ranges)),
+            )
+            .service(
+                web::resource("/forecast/{id}/_generate_numeric_ranges")
+                    .route(web::get().to(generate_numeric_ranges)),
+            )
+            .service(
+                web::resource("/forecast/{id}/_generate_outcomes")
+                    .route(web::get().to(generate_outcomes)),
+            )
+            .service(
+                web::resource("/forecast/{id}/_save_ranges").route(web::post().to(save_ranges)),
+            )
+            .service(web::resource("/forecast/{id}/_metadata").route(web::get().to(metadata)))
+            .service(
+                web::resource("/forecast/{id}/_edit_metadata").route(web::get().to(edit_metadata)),
+            )
+            .service(web::resource("/forecast/{id}/_resolve").route(web::post().to(resolve)))
+            .service(
+                web::resource("/forecast/{id}/_fit_distribution")
+                    .route(web::post().to(fit_distribution)),
+            )
+            .service(
+                web::resource("/forecast/{id}/_update_binary").route(web::post().to(update_binary)),
+            )
+            // The JSON API for scripts and dashboards. It is versioned, so
+            // breaking changes go in a new scope.
+            .service(
+                web::scope("/api/v1")
+                    .app_data(api::json_config())
+                    .app_data(api::path_config())
+                    .app_data(api::query_config())
+                    .service(
+                        web::resource("/openapi.json").route(web::get().to(openapi::openapi_json)),
+                    )
+                    .service(
+                        web::resource("/forecasts")
+                            .route(web::get().to(api::list_forecasts))
+                            .route(web::post().to(api::create_forecast)),
+                    )
+                    .service(
+                        web::resource("/forecasts/{id}")
+                            .route(web::get().to(api::read_forecast))
+                            .route(web::put().to(api::update_forecast))
+                            .route(web::delete().to(api::delete_forecast)),
+                    )
+                    .service(
+                        web::resource("/forecasts/{id}/ranges")
+                            .route(web::get().to(api::read_ranges))
+                            .route(web::put().to(api::update_ranges)),
+                    ),
+            )
             .service(fs::Files::new("/static", "./static").show_files_listing())
     })
     .bind(("127.0.0.1", 8080))?